@@ -4,6 +4,8 @@
 #![cfg_attr(not(feature = "dev"), windows_subsystem = "windows")]
 
 mod screens;
+mod storage;
+mod ui;
 
 use avian2d::prelude::*;
 use bevy::{asset::AssetMetaCheck, prelude::*};
@@ -65,7 +67,7 @@ impl Plugin for AppPlugin {
         ));
 
         // Add game plugins
-        app.add_plugins(screens::plugin);
+        app.add_plugins((storage::plugin, screens::plugin, ui::plugin));
 
         // Disable gravity
         app.insert_resource(Gravity(Vec2::ZERO));
@@ -79,11 +81,7 @@ fn spawn_camera(mut commands: Commands) {
     commands.spawn((Name::new("Default Camera"), DefaultCamera, Camera2d));
 }
 
-fn load_assets(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut next_screen: ResMut<NextState<Screen>>,
-) {
+fn load_assets(mut commands: Commands, asset_server: Res<AssetServer>) {
     // Load fonts
     // NOTE: These may be different later, for now they're the same.
     let ui_font = asset_server.load("fonts/Pixellari.ttf");
@@ -100,5 +98,4 @@ fn load_assets(
         shopper_npc,
         shopper_player,
     });
-    next_screen.set(Screen::MainMenu);
 }
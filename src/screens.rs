@@ -1,24 +1,82 @@
+mod controls;
 mod game_over;
 mod level;
 mod main_menu;
+mod settings;
 mod win;
 
+use std::collections::{BTreeSet, HashMap};
+
+use crate::storage::{Persistent, Storage};
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 #[derive(States, Debug, Clone, Eq, PartialEq, Hash, Default)]
 pub enum Screen {
     #[default]
     Loading,
     MainMenu,
+    Settings,
+    Controls,
     Level,
+    /// Passed through to restart a round, since re-entering [`Screen::Level`] from itself doesn't
+    /// run its enter and exit systems.
+    Restart,
     Win,
     GameOver,
 }
 
+/// Why the last round was lost, shown on the game over screen.
+#[derive(Resource, Clone, Copy, Default)]
+pub enum LossReason {
+    #[default]
+    OutOfTime,
+    CaughtBySecurity,
+}
+
+/// Seed of the last round's random number generator, shown on the results screens so the round
+/// can be replayed from there or by putting it in the level file.
+#[derive(Resource, Clone, Copy, Default)]
+pub struct RunSeed(pub u64);
+
+/// Seed for the next round to reuse instead of rolling a fresh one, used up once it starts.
+#[derive(Resource, Default)]
+pub struct ReplaySeed(pub Option<u64>);
+
+/// Progress kept between sessions, with levels keyed by their file path.
+#[derive(Resource, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    /// Fastest win on each level, in seconds.
+    pub best_times: HashMap<String, f32>,
+    /// Levels unlocked by winning them, sorted so saves keep a stable order. Saved as a list,
+    /// same as before it was a set.
+    pub unlocked_levels: BTreeSet<String>,
+}
+
+impl Profile {
+    /// Records a win on `level`, returning whether it beat the previous best time.
+    pub fn record_win(&mut self, level: &str, time: f32) -> bool {
+        let best_time = self.best_times.entry(level.to_string()).or_insert(f32::MAX);
+        let new_best = time < *best_time;
+        if new_best {
+            *best_time = time;
+        }
+        new_best
+    }
+}
+
+impl Persistent for Profile {
+    const KEY: &'static str = "profile";
+    const VERSION: u32 = 1;
+}
+
 pub fn plugin(app: &mut App) {
     // Add respective screen plugins
     app.add_plugins((
         main_menu::plugin,
+        settings::plugin,
+        controls::plugin,
         level::plugin,
         win::plugin,
         game_over::plugin,
@@ -26,4 +84,70 @@ pub fn plugin(app: &mut App) {
 
     // Initialize Screen state
     app.init_state::<Screen>();
+    app.init_resource::<LossReason>();
+    app.init_resource::<RunSeed>();
+    app.init_resource::<ReplaySeed>();
+
+    let profile = app
+        .world()
+        .resource::<Storage>()
+        .load_or_default::<Profile>();
+    app.insert_resource(profile);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Profile,
+        controls::{Binding, InputBindings},
+        settings::{Difficulty, DisplayMode, Settings},
+    };
+    use crate::storage::{FileStorage, Storage, StorageBackend};
+    use bevy::prelude::*;
+
+    fn custom_bindings() -> InputBindings {
+        InputBindings {
+            interact: vec![Binding::Key(KeyCode::KeyE)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn saves_load_back_unchanged() {
+        let storage = Storage::new(FileStorage::temp("round-trip"));
+
+        let settings = Settings {
+            master_volume: 0.5,
+            sfx_volume: 0.3,
+            display_mode: DisplayMode::Fullscreen,
+            difficulty: Difficulty::Hard,
+            ..Default::default()
+        };
+        let bindings = custom_bindings();
+        let mut profile = Profile::default();
+        profile.record_win("Corner Store", 42.5);
+        profile.unlocked_levels.insert("Corner Store".to_string());
+
+        storage.save(&settings).unwrap();
+        storage.save(&bindings).unwrap();
+        storage.save(&profile).unwrap();
+
+        assert_eq!(storage.load::<Settings>().unwrap(), Some(settings));
+        assert_eq!(storage.load::<InputBindings>().unwrap(), Some(bindings));
+        assert_eq!(storage.load::<Profile>().unwrap(), Some(profile));
+    }
+
+    #[test]
+    fn unversioned_bindings_are_migrated() {
+        let backend = FileStorage::temp("unversioned-bindings");
+        // How bindings were saved before saves were versioned
+        let legacy = ron::ser::to_string_pretty(&custom_bindings(), Default::default()).unwrap();
+        backend.write("bindings", &legacy).unwrap();
+        let storage = Storage::new(backend);
+
+        assert_eq!(
+            storage.load::<InputBindings>().unwrap(),
+            Some(custom_bindings())
+        );
+    }
 }
@@ -0,0 +1,414 @@
+use crate::{
+    GameAssets,
+    screens::Screen,
+    storage::{Persistent, Storage, StorageError},
+    ui::{ButtonClicked, UiButton, button},
+};
+use bevy::{color::palettes::css::*, prelude::*};
+use bevy_enhanced_input::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A single keyboard key or gamepad button bound to an action.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Gamepad(GamepadButton),
+}
+
+impl Binding {
+    pub fn input(self) -> Input {
+        match self {
+            Self::Key(key) => key.into(),
+            Self::Gamepad(button) => button.into(),
+        }
+    }
+
+    /// Whether both bindings come from the same kind of device.
+    fn same_device(self, other: Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::Key(_), Self::Key(_)) | (Self::Gamepad(_), Self::Gamepad(_))
+        )
+    }
+}
+
+impl std::fmt::Display for Binding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Key(key) => write!(f, "{:?}", key),
+            Self::Gamepad(button) => write!(f, "Gamepad {:?}", button),
+        }
+    }
+}
+
+/// Keys and buttons bound to the player's actions, turned into their input actions whenever
+/// the player spawns.
+///
+/// The left stick is always bound on top of these.
+#[derive(Resource, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputBindings {
+    pub accelerate_forward: Vec<Binding>,
+    pub accelerate_backward: Vec<Binding>,
+    pub steer_left: Vec<Binding>,
+    pub steer_right: Vec<Binding>,
+    pub interact: Vec<Binding>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        Self {
+            accelerate_forward: vec![Binding::Key(KeyCode::KeyW), Binding::Key(KeyCode::ArrowUp)],
+            accelerate_backward: vec![
+                Binding::Key(KeyCode::KeyS),
+                Binding::Key(KeyCode::ArrowDown),
+            ],
+            steer_left: vec![
+                Binding::Key(KeyCode::KeyA),
+                Binding::Key(KeyCode::ArrowLeft),
+            ],
+            steer_right: vec![
+                Binding::Key(KeyCode::KeyD),
+                Binding::Key(KeyCode::ArrowRight),
+            ],
+            interact: vec![
+                Binding::Key(KeyCode::Space),
+                Binding::Gamepad(GamepadButton::South),
+            ],
+        }
+    }
+}
+
+impl Persistent for InputBindings {
+    const KEY: &'static str = "bindings";
+    const VERSION: u32 = 1;
+
+    fn migrate(version: u32, contents: &str) -> Result<Self, StorageError> {
+        match version {
+            // Saved without a version, the bindings on their own
+            0 => Ok(ron::from_str(contents)?),
+            _ => Err(StorageError::UnsupportedVersion {
+                key: Self::KEY,
+                version,
+            }),
+        }
+    }
+}
+
+impl InputBindings {
+    /// The action `binding` is already bound to, if any.
+    fn bound_slot(&self, binding: Binding) -> Option<BindingSlot> {
+        BindingSlot::ALL
+            .into_iter()
+            .find(|slot| slot.bindings(self).contains(&binding))
+    }
+}
+
+/// One rebindable direction of a player action.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+enum BindingSlot {
+    AccelerateForward,
+    AccelerateBackward,
+    SteerLeft,
+    SteerRight,
+    Interact,
+}
+
+impl BindingSlot {
+    const ALL: [Self; 5] = [
+        Self::AccelerateForward,
+        Self::AccelerateBackward,
+        Self::SteerLeft,
+        Self::SteerRight,
+        Self::Interact,
+    ];
+
+    fn bindings(self, bindings: &InputBindings) -> &Vec<Binding> {
+        match self {
+            Self::AccelerateForward => &bindings.accelerate_forward,
+            Self::AccelerateBackward => &bindings.accelerate_backward,
+            Self::SteerLeft => &bindings.steer_left,
+            Self::SteerRight => &bindings.steer_right,
+            Self::Interact => &bindings.interact,
+        }
+    }
+
+    fn bindings_mut(self, bindings: &mut InputBindings) -> &mut Vec<Binding> {
+        match self {
+            Self::AccelerateForward => &mut bindings.accelerate_forward,
+            Self::AccelerateBackward => &mut bindings.accelerate_backward,
+            Self::SteerLeft => &mut bindings.steer_left,
+            Self::SteerRight => &mut bindings.steer_right,
+            Self::Interact => &mut bindings.interact,
+        }
+    }
+
+    fn label(self, bindings: &InputBindings) -> String {
+        let bound = self
+            .bindings(bindings)
+            .iter()
+            .map(Binding::to_string)
+            .collect::<Vec<_>>();
+        let bound = if bound.is_empty() {
+            "Unbound".to_string()
+        } else {
+            bound.join(", ")
+        };
+        format!("{}: {}", self, bound)
+    }
+}
+
+impl std::fmt::Display for BindingSlot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str_repr = match self {
+            Self::AccelerateForward => "Accelerate",
+            Self::AccelerateBackward => "Reverse",
+            Self::SteerLeft => "Steer Left",
+            Self::SteerRight => "Steer Right",
+            Self::Interact => "Interact",
+        };
+        write!(f, "{}", str_repr)
+    }
+}
+
+/// Waiting for the next key or gamepad button to bind to `slot`.
+#[derive(Resource)]
+struct Capturing {
+    slot: BindingSlot,
+    /// Skips the frame capturing started on, so the press that started it isn't captured.
+    armed: bool,
+}
+
+#[derive(Component)]
+struct ControlsUI;
+
+#[derive(Component)]
+struct ControlsStatus;
+
+#[derive(Component)]
+struct ResetButton;
+
+#[derive(Component)]
+struct BackButton;
+
+pub fn plugin(app: &mut App) {
+    // Add resources
+    let bindings = app
+        .world()
+        .resource::<Storage>()
+        .load_or_default::<InputBindings>();
+    app.insert_resource(bindings);
+
+    // Menu systems
+    app.add_systems(OnEnter(Screen::Controls), spawn_controls_menu);
+    app.add_systems(OnExit(Screen::Controls), despawn_controls_menu);
+    app.add_systems(
+        Update,
+        (
+            capture_binding.run_if(resource_exists::<Capturing>),
+            update_binding_labels.run_if(resource_changed::<InputBindings>),
+        )
+            .chain()
+            .run_if(in_state(Screen::Controls)),
+    );
+
+    // Button reactions
+    app.add_observer(binding_slot_clicked);
+    app.add_observer(reset_button_clicked);
+    app.add_observer(back_button_clicked);
+}
+
+fn spawn_controls_menu(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    bindings: Res<InputBindings>,
+) {
+    commands
+        .spawn((
+            Name::new("Controls UI"),
+            ControlsUI,
+            Node {
+                width: Val::Vw(100.0),
+                height: Val::Vh(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            children![
+                (
+                    Name::new("Title"),
+                    Text::new("Controls"),
+                    TextColor(WHITE.into()),
+                    TextFont {
+                        font: assets.ui_font.clone(),
+                        font_size: 48.0,
+                        ..Default::default()
+                    },
+                    TextLayout {
+                        justify: JustifyText::Center,
+                        ..Default::default()
+                    },
+                ),
+                (
+                    Name::new("Status"),
+                    ControlsStatus,
+                    Text::new("Select an action to rebind it"),
+                    TextColor(SLATE_GRAY.into()),
+                    TextFont {
+                        font: assets.ui_font.clone(),
+                        font_size: 16.0,
+                        ..Default::default()
+                    },
+                    TextLayout {
+                        justify: JustifyText::Center,
+                        ..Default::default()
+                    },
+                ),
+            ],
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Name::new("Button Container"),
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        padding: UiRect::vertical(Val::Px(32.0)),
+                        row_gap: Val::Px(12.0),
+                        ..Default::default()
+                    },
+                ))
+                .with_children(|parent| {
+                    for slot in BindingSlot::ALL {
+                        parent.spawn((
+                            slot,
+                            button(slot.label(&bindings), assets.ui_font.clone(), 24.0),
+                        ));
+                    }
+                    parent.spawn((
+                        ResetButton,
+                        button("Reset to Defaults", assets.ui_font.clone(), 24.0),
+                    ));
+                    parent.spawn((BackButton, button("Back", assets.ui_font.clone(), 32.0)));
+                });
+        });
+}
+
+fn despawn_controls_menu(mut commands: Commands, query: Single<Entity, With<ControlsUI>>) {
+    commands.entity(query.entity()).despawn();
+    commands.remove_resource::<Capturing>();
+}
+
+fn update_binding_labels(
+    bindings: Res<InputBindings>,
+    mut query: Query<(&BindingSlot, &mut Text)>,
+) {
+    for (slot, mut text) in query.iter_mut() {
+        text.0 = slot.label(&bindings);
+    }
+}
+
+/// Disables every button while capturing, so menu navigation keys can be bound too.
+fn set_buttons_disabled(button_query: &mut Query<&mut UiButton>, disabled: bool) {
+    for mut button in button_query.iter_mut() {
+        button.disabled = disabled;
+    }
+}
+
+fn capture_binding(
+    mut commands: Commands,
+    mut capturing: ResMut<Capturing>,
+    mut bindings: ResMut<InputBindings>,
+    storage: Res<Storage>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepad_query: Query<&Gamepad>,
+    mut button_query: Query<&mut UiButton>,
+    mut status_query: Single<&mut Text, With<ControlsStatus>>,
+) {
+    if !capturing.armed {
+        capturing.armed = true;
+        return;
+    }
+
+    let pressed = keyboard
+        .get_just_pressed()
+        .next()
+        .map(|key| Binding::Key(*key))
+        .or_else(|| {
+            gamepad_query
+                .iter()
+                .find_map(|gamepad| gamepad.get_just_pressed().next())
+                .map(|button| Binding::Gamepad(*button))
+        });
+    let Some(binding) = pressed else {
+        return;
+    };
+
+    let slot = capturing.slot;
+    status_query.0 = if binding == Binding::Key(KeyCode::Escape) {
+        "Cancelled".to_string()
+    } else if let Some(other_slot) = bindings
+        .bound_slot(binding)
+        .filter(|other_slot| *other_slot != slot)
+    {
+        format!("{} is already bound to {}", binding, other_slot)
+    } else {
+        // Replaces the slot's bindings from the same device, keeping the other device's
+        let slot_bindings = slot.bindings_mut(&mut bindings);
+        slot_bindings.retain(|bound| !bound.same_device(binding));
+        slot_bindings.push(binding);
+        storage.save_or_log(&*bindings);
+
+        log::info!("Bound {} to {}", binding, slot);
+        format!("Bound {} to {}", binding, slot)
+    };
+
+    commands.remove_resource::<Capturing>();
+    set_buttons_disabled(&mut button_query, false);
+}
+
+fn binding_slot_clicked(
+    trigger: Trigger<ButtonClicked>,
+    mut commands: Commands,
+    query: Query<&BindingSlot>,
+    mut button_query: Query<&mut UiButton>,
+    mut status_query: Single<&mut Text, With<ControlsStatus>>,
+) {
+    let Ok(slot) = query.get(trigger.target()) else {
+        return;
+    };
+
+    commands.insert_resource(Capturing {
+        slot: *slot,
+        armed: false,
+    });
+    set_buttons_disabled(&mut button_query, true);
+    status_query.0 = format!(
+        "Press a key or gamepad button for {} (Escape cancels)",
+        slot
+    );
+}
+
+fn reset_button_clicked(
+    trigger: Trigger<ButtonClicked>,
+    mut bindings: ResMut<InputBindings>,
+    storage: Res<Storage>,
+    query: Query<(), With<ResetButton>>,
+    mut status_query: Single<&mut Text, With<ControlsStatus>>,
+) {
+    if query.contains(trigger.target()) {
+        *bindings = InputBindings::default();
+        storage.save_or_log(&*bindings);
+        status_query.0 = "Restored the default bindings".to_string();
+    }
+}
+
+fn back_button_clicked(
+    trigger: Trigger<ButtonClicked>,
+    mut next_screen: ResMut<NextState<Screen>>,
+    query: Query<(), With<BackButton>>,
+) {
+    if query.contains(trigger.target()) {
+        next_screen.set(Screen::Settings);
+    }
+}
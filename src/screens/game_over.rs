@@ -1,4 +1,8 @@
-use crate::{GameAssets, screens::Screen};
+use crate::{
+    GameAssets,
+    screens::{LossReason, ReplaySeed, RunSeed, Screen},
+    ui::{ButtonClicked, button},
+};
 use bevy::{color::palettes::css::*, prelude::*};
 
 #[derive(Component)]
@@ -7,36 +11,30 @@ struct GameOverUI;
 #[derive(Component)]
 struct TryAgainButton;
 
+#[derive(Component)]
+struct ReplaySeedButton;
+
 pub fn plugin(app: &mut App) {
     // Menu systems
     app.add_systems(OnEnter(Screen::GameOver), spawn_game_over_screen);
     app.add_systems(OnExit(Screen::GameOver), despawn_game_over_screen);
-    app.add_systems(
-        Update,
-        try_again_button_interaction.run_if(in_state(Screen::GameOver)),
-    );
-}
-
-fn button(label: impl Into<String>, font: Handle<Font>, size: f32) -> impl Bundle {
-    let label_str = label.into();
 
-    (
-        Name::new(format!("{} Button", &label_str)),
-        Button,
-        Text::new(&label_str),
-        TextFont {
-            font: font,
-            font_size: size,
-            ..Default::default()
-        },
-        TextLayout {
-            justify: JustifyText::Center,
-            ..Default::default()
-        },
-    )
+    // Button reactions
+    app.add_observer(try_again_button_clicked);
+    app.add_observer(replay_seed_button_clicked);
 }
 
-fn spawn_game_over_screen(mut commands: Commands, assets: Res<GameAssets>) {
+fn spawn_game_over_screen(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    loss_reason: Res<LossReason>,
+    run_seed: Res<RunSeed>,
+) {
+    let subtitle = match *loss_reason {
+        LossReason::OutOfTime => "You didn't make it in time",
+        LossReason::CaughtBySecurity => "Security escorted you out of the store",
+    };
+
     commands.spawn((
         Name::new("Game Over UI"),
         GameOverUI,
@@ -65,7 +63,7 @@ fn spawn_game_over_screen(mut commands: Commands, assets: Res<GameAssets>) {
             ),
             (
                 Name::new("Subtitle"),
-                Text::new("You didn't make it in time"),
+                Text::new(subtitle),
                 TextColor(GHOST_WHITE.into()),
                 TextFont {
                     font: assets.ui_font.clone(),
@@ -77,6 +75,20 @@ fn spawn_game_over_screen(mut commands: Commands, assets: Res<GameAssets>) {
                     ..Default::default()
                 },
             ),
+            (
+                Name::new("Seed"),
+                Text::new(format!("Seed: {}", run_seed.0)),
+                TextColor(SLATE_GRAY.into()),
+                TextFont {
+                    font: assets.ui_font.clone(),
+                    font_size: 16.0,
+                    ..Default::default()
+                },
+                TextLayout {
+                    justify: JustifyText::Center,
+                    ..Default::default()
+                },
+            ),
             (
                 Name::new("Button Container"),
                 Node {
@@ -85,10 +97,16 @@ fn spawn_game_over_screen(mut commands: Commands, assets: Res<GameAssets>) {
                     row_gap: Val::Px(12.0),
                     ..Default::default()
                 },
-                children![(
-                    TryAgainButton,
-                    button("Try Again", assets.ui_font.clone(), 32.0)
-                ),]
+                children![
+                    (
+                        TryAgainButton,
+                        button("Try Again", assets.ui_font.clone(), 32.0)
+                    ),
+                    (
+                        ReplaySeedButton,
+                        button("Replay Seed", assets.ui_font.clone(), 32.0)
+                    ),
+                ]
             )
         ],
     ));
@@ -98,20 +116,27 @@ fn despawn_game_over_screen(mut commands: Commands, query: Single<Entity, With<G
     commands.entity(query.entity()).despawn();
 }
 
-fn try_again_button_interaction(
+fn try_again_button_clicked(
+    trigger: Trigger<ButtonClicked>,
+    mut next_screen: ResMut<NextState<Screen>>,
+    query: Query<(), With<TryAgainButton>>,
+) {
+    if query.contains(trigger.target()) {
+        log::info!("Starting game...");
+        next_screen.set(Screen::Level);
+    }
+}
+
+fn replay_seed_button_clicked(
+    trigger: Trigger<ButtonClicked>,
+    run_seed: Res<RunSeed>,
+    mut replay_seed: ResMut<ReplaySeed>,
     mut next_screen: ResMut<NextState<Screen>>,
-    mut query: Single<(&Interaction, &mut TextColor), (Changed<Interaction>, With<TryAgainButton>)>,
+    query: Query<(), With<ReplaySeedButton>>,
 ) {
-    match *query.0 {
-        Interaction::Pressed => {
-            log::info!("Starting game...");
-            next_screen.set(Screen::Level);
-        }
-        Interaction::Hovered => {
-            *query.1 = SLATE_GRAY.into();
-        }
-        Interaction::None => {
-            *query.1 = WHITE.into();
-        }
+    if query.contains(trigger.target()) {
+        log::info!("Replaying seed {}...", run_seed.0);
+        replay_seed.0 = Some(run_seed.0);
+        next_screen.set(Screen::Level);
     }
 }
@@ -0,0 +1,430 @@
+use std::{marker::PhantomData, sync::Arc, time::Duration};
+
+use crate::screens::level::LevelState;
+use bevy::{ecs::world::EntityRefExcept, prelude::*};
+
+/// Progress of the action an NPC is currently running.
+///
+/// Inserted as [`ActionStatus::Running`] whenever an action starts. The system driving the
+/// action sets it to [`ActionStatus::Success`] or [`ActionStatus::Failure`] once it's done, and
+/// the behavior tree moves on during [`FixedPostUpdate`].
+#[derive(Component, Reflect, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[reflect(Component)]
+pub enum ActionStatus {
+    #[default]
+    Running,
+    Success,
+    Failure,
+}
+
+impl ActionStatus {
+    pub fn is_running(self) -> bool {
+        self == Self::Running
+    }
+}
+
+/// Leaf of a behavior tree, added to the NPC as a component while it runs.
+pub trait BehaviorAction: Send + Sync + 'static {
+    fn start(&self, entity: &mut EntityCommands);
+    fn stop(&self, entity: &mut EntityCommands);
+}
+
+/// Action that inserts a fresh copy of a component, and removes it again once it's done.
+struct ComponentAction<C: Component + Clone>(C);
+
+impl<C: Component + Clone> BehaviorAction for ComponentAction<C> {
+    fn start(&self, entity: &mut EntityCommands) {
+        entity.insert((self.0.clone(), ActionStatus::Running));
+    }
+
+    fn stop(&self, entity: &mut EntityCommands) {
+        entity.remove::<C>();
+    }
+}
+
+/// Check on the NPC's components that decides whether part of a tree may run.
+pub trait BehaviorCondition: Send + Sync + 'static {
+    fn check(&self, entity: &EntityRefExcept<BehaviorTree>) -> bool;
+}
+
+/// Condition on a single component, which fails if the NPC doesn't have it.
+struct ComponentCondition<C: Component, F: Fn(&C) -> bool>(F, PhantomData<fn(&C)>);
+
+impl<C, F> BehaviorCondition for ComponentCondition<C, F>
+where
+    C: Component,
+    F: Fn(&C) -> bool + Send + Sync + 'static,
+{
+    fn check(&self, entity: &EntityRefExcept<BehaviorTree>) -> bool {
+        entity
+            .get::<C>()
+            .is_some_and(|component| (self.0)(component))
+    }
+}
+
+/// Description of a behavior tree, turned into a [`BehaviorTree`] component with
+/// [`BehaviorTree::new`].
+pub enum Behavior {
+    /// Runs children in order, failing as soon as one fails.
+    Sequence(Vec<Behavior>),
+    /// Tries children in order until one succeeds.
+    Selector(Vec<Behavior>),
+    /// Runs the child forever, starting it over every time it finishes.
+    Repeat(Box<Behavior>),
+    /// Fails the child if it's still running after this many seconds.
+    Timeout(f32, Box<Behavior>),
+    /// Only runs the child while the condition holds, failing without starting it otherwise.
+    Guard {
+        name: &'static str,
+        condition: Arc<dyn BehaviorCondition>,
+        child: Box<Behavior>,
+    },
+    Action {
+        name: &'static str,
+        action: Arc<dyn BehaviorAction>,
+    },
+}
+
+impl Behavior {
+    /// Leaf that runs `component` until its system reports back through [`ActionStatus`].
+    pub fn action<C: Component + Clone>(component: C) -> Self {
+        let name = std::any::type_name::<C>();
+        Self::Action {
+            name: name.rsplit("::").next().unwrap_or(name),
+            action: Arc::new(ComponentAction(component)),
+        }
+    }
+
+    pub fn repeat(child: Behavior) -> Self {
+        Self::Repeat(Box::new(child))
+    }
+
+    pub fn timeout(seconds: f32, child: Behavior) -> Self {
+        Self::Timeout(seconds, Box::new(child))
+    }
+
+    /// Runs `child` only while `condition` holds for the NPC's `C` component.
+    pub fn guard<C: Component>(
+        name: &'static str,
+        condition: impl Fn(&C) -> bool + Send + Sync + 'static,
+        child: Behavior,
+    ) -> Self {
+        Self::Guard {
+            name,
+            condition: Arc::new(ComponentCondition(condition, PhantomData)),
+            child: Box::new(child),
+        }
+    }
+}
+
+/// A node of a [`BehaviorTree`], along with its runtime state.
+#[derive(Reflect, Clone, Debug)]
+pub enum BehaviorNode {
+    Sequence {
+        children: Vec<usize>,
+        current: usize,
+    },
+    Selector {
+        children: Vec<usize>,
+        current: usize,
+    },
+    Repeat {
+        child: usize,
+    },
+    Timeout {
+        child: usize,
+        timer: Timer,
+    },
+    Guard {
+        name: String,
+        condition: usize,
+        child: usize,
+    },
+    Action {
+        name: String,
+        action: usize,
+        running: bool,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum NodeStatus {
+    Running,
+    Success,
+    Failure,
+}
+
+/// Per-entity state handed down the tree while it's ticked.
+struct TickContext<'a, 'w> {
+    entity: EntityCommands<'a>,
+    /// The NPC's other components, for conditions to check.
+    components: &'a EntityRefExcept<'w, BehaviorTree>,
+    delta: Duration,
+    /// Status of the running action, taken once a finished action has been handled.
+    action_status: Option<ActionStatus>,
+}
+
+/// Behavior tree shared by all NPC types, ticked once per frame.
+///
+/// Nodes are stored flat so the whole tree, including its timers, shows up in the inspector.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct BehaviorTree {
+    nodes: Vec<BehaviorNode>,
+    root: usize,
+    /// Action currently running, for inspecting.
+    active_action: Option<String>,
+    #[reflect(ignore)]
+    actions: Vec<Arc<dyn BehaviorAction>>,
+    #[reflect(ignore)]
+    conditions: Vec<Arc<dyn BehaviorCondition>>,
+    interrupted: bool,
+}
+
+impl BehaviorTree {
+    pub fn new(root: Behavior) -> Self {
+        let mut tree = Self {
+            nodes: Vec::new(),
+            root: 0,
+            active_action: None,
+            actions: Vec::new(),
+            conditions: Vec::new(),
+            interrupted: false,
+        };
+        tree.root = tree.add(root);
+        tree
+    }
+
+    fn add(&mut self, behavior: Behavior) -> usize {
+        let node = match behavior {
+            Behavior::Sequence(children) => BehaviorNode::Sequence {
+                children: children.into_iter().map(|child| self.add(child)).collect(),
+                current: 0,
+            },
+            Behavior::Selector(children) => BehaviorNode::Selector {
+                children: children.into_iter().map(|child| self.add(child)).collect(),
+                current: 0,
+            },
+            Behavior::Repeat(child) => BehaviorNode::Repeat {
+                child: self.add(*child),
+            },
+            Behavior::Timeout(seconds, child) => BehaviorNode::Timeout {
+                child: self.add(*child),
+                timer: Timer::from_seconds(seconds, TimerMode::Once),
+            },
+            Behavior::Guard {
+                name,
+                condition,
+                child,
+            } => {
+                self.conditions.push(condition);
+                BehaviorNode::Guard {
+                    name: name.to_string(),
+                    condition: self.conditions.len() - 1,
+                    child: self.add(*child),
+                }
+            }
+            Behavior::Action { name, action } => {
+                self.actions.push(action);
+                BehaviorNode::Action {
+                    name: name.to_string(),
+                    action: self.actions.len() - 1,
+                    running: false,
+                }
+            }
+        };
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    /// Stops whatever is running and starts over from the root on the next tick, e.g. when
+    /// something more urgent comes up.
+    pub fn interrupt(&mut self) {
+        self.interrupted = true;
+    }
+
+    fn tick(&mut self, context: &mut TickContext) {
+        if self.interrupted {
+            self.interrupted = false;
+            self.reset(self.root, context);
+        }
+
+        self.tick_node(self.root, context);
+    }
+
+    fn tick_node(&mut self, index: usize, context: &mut TickContext) -> NodeStatus {
+        match self.nodes[index] {
+            BehaviorNode::Sequence { .. } | BehaviorNode::Selector { .. } => {
+                self.tick_composite(index, context)
+            }
+            BehaviorNode::Repeat { child } => {
+                // Start over on the next frame, so a child that finishes instantly can't hang
+                if self.tick_node(child, context) != NodeStatus::Running {
+                    self.reset(child, context);
+                }
+                NodeStatus::Running
+            }
+            BehaviorNode::Timeout {
+                child,
+                ref mut timer,
+            } => {
+                if timer.tick(context.delta).finished() {
+                    self.reset(index, context);
+                    return NodeStatus::Failure;
+                }
+
+                let status = self.tick_node(child, context);
+                if status != NodeStatus::Running {
+                    self.reset(index, context);
+                }
+                status
+            }
+            BehaviorNode::Guard {
+                condition, child, ..
+            } => {
+                if !self.conditions[condition].check(context.components) {
+                    self.reset(child, context);
+                    return NodeStatus::Failure;
+                }
+                self.tick_node(child, context)
+            }
+            BehaviorNode::Action {
+                ref name,
+                action,
+                ref mut running,
+            } => {
+                if !*running {
+                    *running = true;
+                    self.active_action = Some(name.clone());
+                    self.actions[action].start(&mut context.entity);
+                    context.action_status = Some(ActionStatus::Running);
+                    return NodeStatus::Running;
+                }
+
+                let status = match context.action_status {
+                    None | Some(ActionStatus::Running) => return NodeStatus::Running,
+                    Some(ActionStatus::Success) => NodeStatus::Success,
+                    Some(ActionStatus::Failure) => NodeStatus::Failure,
+                };
+                context.action_status = None;
+                self.reset(index, context);
+                status
+            }
+        }
+    }
+
+    fn tick_composite(&mut self, index: usize, context: &mut TickContext) -> NodeStatus {
+        loop {
+            let (child, is_sequence) = match &self.nodes[index] {
+                BehaviorNode::Sequence { children, current } => (children.get(*current), true),
+                BehaviorNode::Selector { children, current } => (children.get(*current), false),
+                _ => unreachable!(),
+            };
+            let Some(&child) = child else {
+                // Every child succeeded for a sequence, or failed for a selector
+                self.reset(index, context);
+                return if is_sequence {
+                    NodeStatus::Success
+                } else {
+                    NodeStatus::Failure
+                };
+            };
+
+            match (self.tick_node(child, context), is_sequence) {
+                (NodeStatus::Running, _) => return NodeStatus::Running,
+                (NodeStatus::Failure, true) => {
+                    self.reset(index, context);
+                    return NodeStatus::Failure;
+                }
+                (NodeStatus::Success, false) => {
+                    self.reset(index, context);
+                    return NodeStatus::Success;
+                }
+                _ => {
+                    if let BehaviorNode::Sequence { current, .. }
+                    | BehaviorNode::Selector { current, .. } = &mut self.nodes[index]
+                    {
+                        *current += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Puts a node and everything under it back to how it started, stopping any running action.
+    fn reset(&mut self, index: usize, context: &mut TickContext) {
+        match self.nodes[index] {
+            BehaviorNode::Sequence {
+                ref children,
+                ref mut current,
+            }
+            | BehaviorNode::Selector {
+                ref children,
+                ref mut current,
+            } => {
+                *current = 0;
+                for child in children.clone() {
+                    self.reset(child, context);
+                }
+            }
+            BehaviorNode::Repeat { child } | BehaviorNode::Guard { child, .. } => {
+                self.reset(child, context)
+            }
+            BehaviorNode::Timeout {
+                child,
+                ref mut timer,
+            } => {
+                timer.reset();
+                self.reset(child, context);
+            }
+            BehaviorNode::Action {
+                action,
+                ref mut running,
+                ..
+            } => {
+                if *running {
+                    *running = false;
+                    self.active_action = None;
+                    self.actions[action].stop(&mut context.entity);
+                }
+            }
+        }
+    }
+}
+
+pub fn plugin(app: &mut App) {
+    // Register necessary types
+    app.register_type::<ActionStatus>();
+    app.register_type::<BehaviorTree>();
+
+    // Behavior systems, after gameplay systems have reported on their actions
+    app.add_systems(
+        FixedPostUpdate,
+        tick_behavior_trees.run_if(in_state(LevelState::Playing)),
+    );
+}
+
+fn tick_behavior_trees(
+    time: Res<Time>,
+    par_commands: ParallelCommands,
+    mut tree_query: Query<(
+        Entity,
+        &mut BehaviorTree,
+        Option<&ActionStatus>,
+        EntityRefExcept<BehaviorTree>,
+    )>,
+) {
+    tree_query
+        .par_iter_mut()
+        .for_each(|(entity, mut tree, action_status, components)| {
+            par_commands.command_scope(|mut commands| {
+                let mut context = TickContext {
+                    entity: commands.entity(entity),
+                    components: &components,
+                    delta: time.delta(),
+                    action_status: action_status.copied(),
+                };
+                tree.tick(&mut context);
+            });
+        });
+}
@@ -1,49 +1,173 @@
-use crate::screens::{
-    Screen,
-    level::{EntityOrientation, GameLayer},
+use crate::{
+    GameAssets,
+    screens::{
+        Screen,
+        level::{
+            EntityOrientation, GameLayer, Inventory, LevelState, Objectives,
+            guard::SecurityAlarm,
+            navigation::NavObstacle,
+            player::Player,
+            round::{RoundEnded, RoundOutcome},
+            shopper::Shopper,
+        },
+    },
 };
 use avian2d::prelude::*;
 use bevy::{color::palettes::css::*, prelude::*};
+use serde::Deserialize;
 
-#[derive(Event)]
+const COUNTER_SIZE: Vec2 = Vec2::new(140.0, 60.0);
+const QUEUE_SPACING: f32 = 80.0;
+
+#[derive(Event, Clone, Deserialize)]
 pub struct SpawnCheckoutCounter {
     pub position: Vec2,
     pub orientation: EntityOrientation,
+    /// Time in seconds the cashier takes to serve one customer.
+    pub checkout_duration: f32,
 }
 
+/// Area on the exit side of the checkouts, which the player shouldn't enter with unpaid items.
+#[derive(Event, Clone, Deserialize)]
+pub struct SpawnCheckoutArea {
+    pub position: Vec2,
+    pub size: Vec2,
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct CheckoutCounter {
+    /// Customers waiting in line, the first one is being served.
+    pub queue: Vec<Entity>,
+    pub checkout_duration: f32,
+}
+
+#[derive(Component)]
+struct CheckoutLane;
+
 #[derive(Component)]
-struct CheckoutCounter;
+struct CheckoutArea;
+
+/// Added to the player while they are being served at a checkout counter.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct CheckingOut {
+    pub counter: Entity,
+    timer: Timer,
+}
+
+impl CheckingOut {
+    pub fn new(counter: Entity, duration: f32) -> Self {
+        Self {
+            counter,
+            timer: Timer::from_seconds(duration, TimerMode::Once),
+        }
+    }
+}
 
 pub fn plugin(app: &mut App) {
+    // Register necessary types
+    app.register_type::<CheckoutCounter>();
+    app.register_type::<CheckingOut>();
+
     // Register events
     app.add_event::<SpawnCheckoutCounter>();
+    app.add_event::<SpawnCheckoutArea>();
 
     // Checkout counter systems
+    app.add_systems(
+        FixedUpdate,
+        (
+            spawn_checkout_counters.run_if(on_event::<SpawnCheckoutCounter>),
+            spawn_checkout_areas.run_if(on_event::<SpawnCheckoutArea>),
+            (
+                update_checkout_queues,
+                resize_checkout_lanes.after(update_checkout_queues),
+                player_checking_out.after(update_checkout_queues),
+            )
+                .run_if(in_state(LevelState::Playing)),
+        )
+            .run_if(in_state(Screen::Level)),
+    );
     app.add_systems(
         Update,
-        spawn_checkout_counters
-            .run_if(in_state(Screen::Level).and(on_event::<SpawnCheckoutCounter>)),
+        checkout_counter_label.run_if(in_state(Screen::Level)),
+    );
+    app.add_systems(
+        OnExit(Screen::Level),
+        (despawn_checkout_counters, despawn_checkout_areas),
     );
-    app.add_systems(OnExit(Screen::Level), despawn_checkout_counters);
 }
 
-fn spawn_checkout_counters(mut commands: Commands, mut events: EventReader<SpawnCheckoutCounter>) {
-    let counter_size = Vec2::new(140.0, 60.0);
+/// World position of the `index`-th place in a counter's queue, the first place being right in
+/// front of the counter.
+pub fn queue_slot_position(counter_transform: &Transform, index: usize) -> Vec2 {
+    let offset = COUNTER_SIZE.y / 2.0 + QUEUE_SPACING * (index as f32 + 0.5);
+    counter_transform
+        .transform_point(Vec3::new(0.0, -offset, 0.0))
+        .truncate()
+}
+
+/// Size of a queue lane covering `slots` places in line.
+fn lane_size(slots: usize) -> Vec2 {
+    Vec2::new(COUNTER_SIZE.x, slots as f32 * QUEUE_SPACING)
+}
+
+fn spawn_checkout_counters(
+    mut commands: Commands,
+    mut events: EventReader<SpawnCheckoutCounter>,
+    assets: Res<GameAssets>,
+) {
+    // Room for the first customer, grown as the line gets longer
+    let lane_size = lane_size(1);
 
     for event in events.read() {
-        commands.spawn((
-            Name::new("Checkout Counter"),
-            CheckoutCounter,
-            Sprite::from_color(ORANGE_RED, counter_size),
-            Transform {
-                translation: event.position.extend(0.0),
-                rotation: event.orientation.into(),
-                ..Default::default()
-            },
-            RigidBody::Static,
-            Collider::rectangle(counter_size.x, counter_size.y),
-            CollisionLayers::new(GameLayer::Environment, [GameLayer::Player, GameLayer::NPC]),
-        ));
+        commands
+            .spawn((
+                Name::new("Checkout Counter"),
+                CheckoutCounter {
+                    queue: Vec::new(),
+                    checkout_duration: event.checkout_duration,
+                },
+                Sprite::from_color(ORANGE_RED, COUNTER_SIZE),
+                Transform {
+                    translation: event.position.extend(0.0),
+                    rotation: event.orientation.into(),
+                    ..Default::default()
+                },
+                RigidBody::Static,
+                Collider::rectangle(COUNTER_SIZE.x, COUNTER_SIZE.y),
+                CollisionLayers::new(
+                    GameLayer::Environment,
+                    [GameLayer::Player, GameLayer::NPC, GameLayer::Pickup],
+                ),
+                NavObstacle { size: COUNTER_SIZE },
+                Text2d::new("Checkout"),
+                TextFont {
+                    font: assets.game_font.clone(),
+                    font_size: 20.0,
+                    ..Default::default()
+                },
+            ))
+            .with_children(|parent| {
+                // Customers line up in this lane in front of the counter
+                parent
+                    .spawn((
+                        Name::new("Checkout Lane"),
+                        CheckoutLane,
+                        Transform::from_xyz(0.0, -(COUNTER_SIZE.y + lane_size.y) / 2.0, 0.0),
+                        Sensor,
+                        Collider::rectangle(lane_size.x, lane_size.y),
+                        CollisionLayers::new(
+                            GameLayer::Environment,
+                            [GameLayer::Player, GameLayer::NPC],
+                        ),
+                        CollisionEventsEnabled,
+                        // Sprite::from_color(LIGHT_BLUE.with_alpha(0.5), lane_size),
+                    ))
+                    .observe(customer_approached_counter)
+                    .observe(customer_departed_counter);
+            });
     }
 }
 
@@ -52,3 +176,217 @@ fn despawn_checkout_counters(mut commands: Commands, query: Query<Entity, With<C
         commands.entity(entity).despawn();
     }
 }
+
+fn spawn_checkout_areas(mut commands: Commands, mut events: EventReader<SpawnCheckoutArea>) {
+    for event in events.read() {
+        commands
+            .spawn((
+                Name::new("Checkout Area"),
+                CheckoutArea,
+                Transform::from_translation(event.position.extend(-1.0)),
+                RigidBody::Static,
+                Sensor,
+                Collider::rectangle(event.size.x, event.size.y),
+                CollisionLayers::new(GameLayer::Environment, [GameLayer::Player]),
+                CollisionEventsEnabled,
+                // Sprite::from_color(LIGHT_CORAL.with_alpha(0.3), event.size),
+            ))
+            .observe(player_entered_checkout_area)
+            .observe(player_left_checkout_area);
+    }
+}
+
+fn despawn_checkout_areas(mut commands: Commands, query: Query<Entity, With<CheckoutArea>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn customer_approached_counter(
+    trigger: Trigger<OnCollisionStart>,
+    child_of_query: Query<&ChildOf>,
+    mut player_query: Query<&mut Player>,
+) {
+    let lane_entity = trigger.target();
+
+    if let Ok(lane_child_of) = child_of_query.get(lane_entity) {
+        if let Ok(mut player) = player_query.get_mut(trigger.collider) {
+            log::debug!("Player has approached checkout lane {}", lane_entity);
+            player.current_counter = Some(lane_child_of.parent());
+        }
+    }
+}
+
+fn customer_departed_counter(
+    trigger: Trigger<OnCollisionEnd>,
+    child_of_query: Query<&ChildOf>,
+    mut player_query: Query<&mut Player>,
+) {
+    let lane_entity = trigger.target();
+
+    if let Ok(lane_child_of) = child_of_query.get(lane_entity) {
+        if let Ok(mut player) = player_query.get_mut(trigger.collider) {
+            // Leaving another counter's lane doesn't take the player out of this one
+            if player.current_counter == Some(lane_child_of.parent()) {
+                log::debug!("Player has departed checkout lane {}", lane_entity);
+                player.current_counter = None;
+            }
+        }
+    }
+}
+
+fn update_checkout_queues(
+    mut counter_query: Query<(Entity, &mut CheckoutCounter)>,
+    player_query: Query<(Entity, &Player)>,
+    shopper_query: Query<(Entity, &Shopper)>,
+) {
+    // Every customer that wants to be in a queue, along with the counter they're queuing at
+    let customers = player_query
+        .iter()
+        .filter_map(|(entity, player)| player.queued_counter.map(|counter| (entity, counter)))
+        .chain(shopper_query.iter().filter_map(|(entity, shopper)| {
+            shopper.queued_counter.map(|counter| (entity, counter))
+        }))
+        .collect::<Vec<_>>();
+
+    for (counter_entity, mut counter) in counter_query.iter_mut() {
+        // Drop customers that left the line, keeping everyone else in order
+        let still_queuing = |customer: &Entity| {
+            customers
+                .iter()
+                .any(|&(entity, queued_at)| entity == *customer && queued_at == counter_entity)
+        };
+        if !counter.queue.iter().all(still_queuing) {
+            counter.queue.retain(still_queuing);
+        }
+
+        // New customers join at the back
+        for &(entity, _) in customers
+            .iter()
+            .filter(|(_, queued_at)| *queued_at == counter_entity)
+        {
+            if !counter.queue.contains(&entity) {
+                counter.queue.push(entity);
+            }
+        }
+    }
+}
+
+/// Stretches each counter's lane over its whole line, plus room at the back for one more
+/// customer to join.
+fn resize_checkout_lanes(
+    counter_query: Query<(&CheckoutCounter, &Children), Changed<CheckoutCounter>>,
+    mut lane_query: Query<(&mut Transform, &mut Collider), With<CheckoutLane>>,
+) {
+    for (counter, children) in counter_query.iter() {
+        let lane_size = lane_size(counter.queue.len() + 1);
+        for child in children.iter() {
+            if let Ok((mut lane_transform, mut lane_collider)) = lane_query.get_mut(child) {
+                lane_transform.translation.y = -(COUNTER_SIZE.y + lane_size.y) / 2.0;
+                *lane_collider = Collider::rectangle(lane_size.x, lane_size.y);
+            }
+        }
+    }
+}
+
+/// Calls security when the player goes past the checkouts with items they haven't paid for.
+fn player_entered_checkout_area(
+    trigger: Trigger<OnCollisionStart>,
+    mut commands: Commands,
+    mut player_query: Query<(&mut Player, &Inventory)>,
+) {
+    let Ok((mut player, inventory)) = player_query.get_mut(trigger.collider) else {
+        return;
+    };
+
+    player.past_checkout = true;
+    if player.has_unpaid_items(inventory) {
+        log::info!("Player went past the checkout without paying");
+        commands.trigger(SecurityAlarm::Shoplifting);
+    }
+}
+
+fn player_left_checkout_area(
+    trigger: Trigger<OnCollisionEnd>,
+    mut player_query: Query<&mut Player>,
+) {
+    if let Ok(mut player) = player_query.get_mut(trigger.collider) {
+        player.past_checkout = false;
+    }
+}
+
+fn player_checking_out(
+    mut commands: Commands,
+    time: Res<Time>,
+    objectives: Res<Objectives>,
+    mut player_query: Query<(Entity, &mut Player, &Inventory, Option<&mut CheckingOut>)>,
+    counter_query: Query<&CheckoutCounter>,
+    mut round_events: EventWriter<RoundEnded>,
+) {
+    for (player_entity, mut player, inventory, checking_out) in player_query.iter_mut() {
+        let Some(counter_entity) = player.queued_counter else {
+            continue;
+        };
+
+        // Walking out of the lane means leaving the line, which is fine as long as the player
+        // doesn't go past the counter
+        if player.current_counter != Some(counter_entity) {
+            log::info!("Player left the checkout line before paying");
+            player.queued_counter = None;
+            commands.entity(player_entity).remove::<CheckingOut>();
+            continue;
+        }
+
+        let Ok(counter) = counter_query.get(counter_entity) else {
+            continue;
+        };
+
+        // Wait until the cashier is free
+        if counter.queue.first() != Some(&player_entity) {
+            continue;
+        }
+
+        let Some(mut checking_out) = checking_out else {
+            log::info!("Player is being served at {}", counter_entity);
+            commands
+                .entity(player_entity)
+                .insert(CheckingOut::new(counter_entity, counter.checkout_duration));
+            continue;
+        };
+
+        if checking_out.timer.tick(time.delta()).just_finished() {
+            player.queued_counter = None;
+            player.paid_items = inventory.0.clone();
+            commands.entity(player_entity).remove::<CheckingOut>();
+
+            if objectives.fulfilled_by(inventory) {
+                round_events.write(RoundEnded(RoundOutcome::Won));
+            } else {
+                log::info!("Player checked out without all required items");
+            }
+        }
+    }
+}
+
+fn checkout_counter_label(
+    mut counter_query: Query<(Entity, &CheckoutCounter, &mut Text2d)>,
+    checking_out_query: Query<&CheckingOut>,
+) {
+    for (counter_entity, counter, mut text) in counter_query.iter_mut() {
+        let paying = counter
+            .queue
+            .first()
+            .and_then(|customer| checking_out_query.get(*customer).ok())
+            .filter(|checking_out| checking_out.counter == counter_entity);
+
+        let label = match paying {
+            Some(checking_out) => {
+                format!("Paying... {:.0}%", checking_out.timer.fraction() * 100.0)
+            }
+            None => format!("Checkout ({})", counter.queue.len()),
+        };
+        if text.0 != label {
+            text.0 = label;
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use crate::{
+    GameAssets,
+    screens::{
+        Screen,
+        level::{
+            GameLayer, Inventory, Item,
+            player::{Player, Stunned},
+            rng::GameRng,
+            shopper::{PanicTierChanged, Shopper},
+        },
+    },
+};
+use avian2d::prelude::*;
+use bevy::{color::palettes::css::*, prelude::*};
+
+#[derive(Event)]
+pub struct SpawnDroppedItem {
+    pub position: Vec2,
+    /// Initial velocity, so dropped items can be flung across the floor.
+    pub velocity: Vec2,
+    pub item: Item,
+    pub quantity: u32,
+}
+
+/// Items lying on the floor that the player or a shopper can collect by driving over them.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct DroppedItem {
+    pub item: Item,
+    pub quantity: u32,
+}
+
+pub fn plugin(app: &mut App) {
+    // Register necessary types
+    app.register_type::<DroppedItem>();
+
+    // Register spawn events
+    app.add_event::<SpawnDroppedItem>();
+
+    // Dropped item systems
+    app.add_systems(
+        FixedUpdate,
+        spawn_dropped_items
+            .run_if(on_event::<SpawnDroppedItem>)
+            .run_if(in_state(Screen::Level)),
+    );
+    app.add_systems(OnExit(Screen::Level), despawn_dropped_items);
+
+    // Add observers
+    app.add_observer(shopper_dropping_loot);
+}
+
+fn spawn_dropped_items(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    mut events: EventReader<SpawnDroppedItem>,
+) {
+    let item_size = Vec2::splat(24.0);
+
+    for event in events.read() {
+        let label = if event.quantity > 1 {
+            format!("{} x{}", event.item, event.quantity)
+        } else {
+            event.item.to_string()
+        };
+
+        commands
+            .spawn((
+                Name::new(format!("Dropped {}", label)),
+                DroppedItem {
+                    item: event.item,
+                    quantity: event.quantity,
+                },
+                Sprite::from_color(KHAKI, item_size),
+                Transform::from_translation(event.position.extend(-0.5)),
+                RigidBody::Dynamic,
+                Collider::rectangle(item_size.x, item_size.y),
+                CollisionLayers::new(
+                    GameLayer::Pickup,
+                    [GameLayer::Player, GameLayer::NPC, GameLayer::Environment],
+                ),
+                CollisionEventsEnabled,
+                LinearVelocity(event.velocity),
+                LinearDamping(3.0),
+                AngularDamping(3.0),
+                children![(
+                    Name::new("Dropped Item Label"),
+                    Text2d::new(label),
+                    TextColor(WHITE.into()),
+                    TextFont {
+                        font: assets.game_font.clone(),
+                        font_size: 12.0,
+                        ..Default::default()
+                    },
+                    Transform::from_xyz(0.0, item_size.y, 0.0),
+                )],
+            ))
+            .observe(picked_up_dropped_item);
+    }
+}
+
+fn despawn_dropped_items(mut commands: Commands, query: Query<Entity, With<DroppedItem>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn picked_up_dropped_item(
+    trigger: Trigger<OnCollisionStart>,
+    mut commands: Commands,
+    mut dropped_item_query: Query<&mut DroppedItem>,
+    mut collector_query: Query<
+        &mut Inventory,
+        (Or<(With<Player>, With<Shopper>)>, Without<Stunned>),
+    >,
+) {
+    let Ok(mut dropped_item) = dropped_item_query.get_mut(trigger.target()) else {
+        return;
+    };
+
+    // Already collected by someone else this frame
+    if dropped_item.quantity == 0 {
+        return;
+    }
+
+    if let Ok(mut inventory) = collector_query.get_mut(trigger.collider) {
+        log::debug!(
+            "{} picked up {} dropped {}",
+            trigger.collider,
+            dropped_item.quantity,
+            dropped_item.item
+        );
+        inventory
+            .0
+            .entry(dropped_item.item)
+            .and_modify(|e| *e += dropped_item.quantity)
+            .or_insert(dropped_item.quantity);
+        dropped_item.quantity = 0;
+        commands.entity(trigger.target()).despawn();
+    }
+}
+
+/// Shoppers that panic enough to flee or stampede drop everything they were carrying, once as
+/// they start panicking.
+fn shopper_dropping_loot(
+    trigger: Trigger<PanicTierChanged>,
+    mut shopper_query: Query<(&Transform, &mut Inventory)>,
+    mut game_rng: ResMut<GameRng>,
+    mut dropped_item_events: EventWriter<SpawnDroppedItem>,
+) {
+    if trigger.from.is_panicking() || !trigger.to.is_panicking() {
+        return;
+    }
+    let Ok((shopper_transform, mut inventory)) = shopper_query.get_mut(trigger.target()) else {
+        return;
+    };
+
+    let dropped_items = inventory.drain_sorted();
+
+    let position = shopper_transform.translation.truncate();
+    dropped_item_events.write_batch(dropped_items.into_iter().map(|(item, quantity)| {
+        let direction = Vec2::from_angle(game_rng.f32() * std::f32::consts::TAU);
+        SpawnDroppedItem {
+            position: position + direction * 50.0,
+            velocity: direction * 200.0,
+            item,
+            quantity,
+        }
+    }));
+}
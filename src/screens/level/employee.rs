@@ -0,0 +1,427 @@
+use crate::{
+    GameAssets,
+    screens::{
+        Screen,
+        level::{
+            GameLayer, LevelState,
+            behavior::{ActionStatus, Behavior, BehaviorTree},
+            navigation::{NavGrid, NavPath, follow_path},
+            shelf::Shelf,
+            steering::Steering,
+        },
+    },
+};
+use avian2d::prelude::*;
+use bevy::{color::palettes::css::*, prelude::*};
+use serde::Deserialize;
+
+/// Shelves at or below this fraction of their capacity get restocked.
+const LOW_STOCK_FRACTION: f32 = 0.25;
+/// Seconds it takes an employee to put one item back on a shelf.
+const RESTOCK_INTERVAL: f32 = 0.5;
+/// How close an employee needs to be to a stockroom to count as inside it.
+const STOCKROOM_RADIUS: f32 = 60.0;
+/// Seconds between an idle employee's checks on the shelves.
+const IDLE_INTERVAL: f32 = 1.0;
+/// Seconds an employee tries to reach a shelf before giving up on it, so a blocked employee
+/// doesn't keep the shelf claimed forever.
+const DELIVERY_TIMEOUT: f32 = 20.0;
+
+#[derive(Event, Clone, Deserialize)]
+pub struct SpawnStockroom {
+    pub position: Vec2,
+}
+
+#[derive(Event, Clone, Deserialize)]
+pub struct SpawnEmployee {
+    pub position: Vec2,
+}
+
+#[derive(Component)]
+pub struct Stockroom;
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Employee {
+    pub current_shelf: Option<Entity>,
+    /// Shelf the employee has claimed to restock.
+    target_shelf: Option<Entity>,
+}
+
+/// Restocks shelves running low, giving up on any it can't reach in time.
+fn employee_behavior() -> Behavior {
+    Behavior::repeat(Behavior::Selector(vec![
+        Behavior::Sequence(vec![
+            Behavior::action(Idle::default()),
+            Behavior::timeout(DELIVERY_TIMEOUT, Behavior::action(Delivering)),
+            Behavior::action(Restocking::default()),
+            Behavior::action(Returning::default()),
+        ]),
+        // Head back for more stock after giving up on a shelf
+        Behavior::action(Returning::default()),
+    ]))
+}
+
+// Employee actions, run by the employee's behavior tree
+
+/// Waiting in the stockroom for a shelf to run low.
+#[derive(Component, Reflect, Clone)]
+#[reflect(Component)]
+struct Idle {
+    timer: Timer,
+}
+
+impl Default for Idle {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(IDLE_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+/// Carrying stock to the claimed shelf.
+#[derive(Component, Reflect, Clone, Default)]
+#[reflect(Component)]
+struct Delivering;
+
+/// Putting items back on the claimed shelf.
+#[derive(Component, Reflect, Clone)]
+#[reflect(Component)]
+struct Restocking {
+    timer: Timer,
+}
+
+impl Default for Restocking {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(RESTOCK_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+/// Walking back to the closest stockroom for more stock.
+#[derive(Component, Reflect, Clone, Default)]
+#[reflect(Component)]
+struct Returning {
+    stockroom: Option<Entity>,
+}
+
+pub fn plugin(app: &mut App) {
+    // Register necessary types
+    app.register_type::<Employee>();
+    app.register_type::<Idle>();
+    app.register_type::<Delivering>();
+    app.register_type::<Restocking>();
+    app.register_type::<Returning>();
+
+    // Register spawn events
+    app.add_event::<SpawnStockroom>();
+    app.add_event::<SpawnEmployee>();
+
+    // Employee systems
+    app.add_systems(
+        FixedUpdate,
+        (
+            spawn_stockrooms.run_if(on_event::<SpawnStockroom>),
+            spawn_employees.run_if(on_event::<SpawnEmployee>),
+            (
+                employee_idling,
+                employee_delivering,
+                employee_restocking,
+                employee_returning,
+            )
+                .run_if(in_state(LevelState::Playing)),
+        )
+            .run_if(in_state(Screen::Level)),
+    );
+    app.add_systems(
+        OnExit(Screen::Level),
+        (despawn_stockrooms, despawn_employees),
+    );
+}
+
+fn spawn_stockrooms(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    mut events: EventReader<SpawnStockroom>,
+) {
+    let stockroom_size = Vec2::splat(2.0 * STOCKROOM_RADIUS);
+
+    for event in events.read() {
+        commands.spawn((
+            Name::new("Stockroom"),
+            Stockroom,
+            Sprite::from_color(SADDLE_BROWN.with_alpha(0.6), stockroom_size),
+            Transform::from_translation(event.position.extend(-1.0)),
+            Text2d::new("Stockroom"),
+            TextFont {
+                font: assets.game_font.clone(),
+                font_size: 20.0,
+                ..Default::default()
+            },
+        ));
+    }
+}
+
+fn despawn_stockrooms(mut commands: Commands, query: Query<Entity, With<Stockroom>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn spawn_employees(mut commands: Commands, mut events: EventReader<SpawnEmployee>) {
+    let employee_size = Vec2::new(72.0, 36.0);
+
+    for event in events.read() {
+        commands.spawn((
+            Name::new("Employee"),
+            Employee {
+                current_shelf: None,
+                target_shelf: None,
+            },
+            BehaviorTree::new(employee_behavior()),
+            Sprite::from_color(DODGER_BLUE, employee_size),
+            Transform::from_translation(event.position.extend(0.0)),
+            RigidBody::Dynamic,
+            Collider::rectangle(employee_size.x, employee_size.y),
+            CollisionLayers::new(
+                GameLayer::NPC,
+                [
+                    GameLayer::Default,
+                    GameLayer::Player,
+                    GameLayer::NPC,
+                    GameLayer::Environment,
+                ],
+            ),
+            CollisionEventsEnabled,
+            LinearDamping(1.2),
+            AngularDamping(2.0),
+            MaxLinearSpeed(120.0),
+            ExternalImpulse::default().with_persistence(false),
+            NavPath::default(),
+            Steering::default(),
+        ));
+    }
+}
+
+fn despawn_employees(mut commands: Commands, query: Query<Entity, With<Employee>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn employee_idling(
+    time: Res<Time>,
+    mut employee_query: Query<(
+        &mut Employee,
+        Option<(&mut Idle, &mut ActionStatus)>,
+        Has<Delivering>,
+        Has<Restocking>,
+    )>,
+    shelf_query: Query<(Entity, &Shelf)>,
+) {
+    // Shelves already being looked after by an employee
+    let mut claimed_shelves = employee_query
+        .iter()
+        .filter(|(_, _, delivering, restocking)| *delivering || *restocking)
+        .filter_map(|(employee, ..)| employee.target_shelf)
+        .collect::<Vec<_>>();
+
+    // Employees claim shelves one after another, so this can't run in parallel
+    for (mut employee, idle, ..) in employee_query.iter_mut() {
+        let Some((mut idle, mut status)) = idle else {
+            continue;
+        };
+        if !status.is_running() || !idle.timer.tick(time.delta()).just_finished() {
+            // Continue idling
+            continue;
+        }
+
+        // Pick the emptiest unclaimed shelf that is running low
+        let target_shelf = shelf_query
+            .iter()
+            .filter(|(shelf_entity, _)| !claimed_shelves.contains(shelf_entity))
+            .map(|(shelf_entity, shelf)| {
+                (
+                    shelf_entity,
+                    shelf.stock as f32 / shelf.capacity.max(1) as f32,
+                )
+            })
+            .filter(|(_, fill)| *fill <= LOW_STOCK_FRACTION)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(shelf_entity, _)| shelf_entity);
+
+        if let Some(target_shelf) = target_shelf {
+            claimed_shelves.push(target_shelf);
+            employee.target_shelf = Some(target_shelf);
+            *status = ActionStatus::Success;
+        }
+    }
+}
+
+fn employee_delivering(
+    nav_grid: Res<NavGrid>,
+    mut employee_query: Query<
+        (
+            &mut ActionStatus,
+            &Employee,
+            &mut Transform,
+            &mut ExternalImpulse,
+            &mut NavPath,
+            &Steering,
+        ),
+        With<Delivering>,
+    >,
+    shelf_query: Query<&Transform, (With<Shelf>, Without<Employee>)>,
+) {
+    employee_query.par_iter_mut().for_each(
+        |(
+            mut status,
+            employee,
+            mut employee_transform,
+            mut employee_impulse,
+            mut nav_path,
+            steering,
+        )| {
+            if !status.is_running() {
+                return;
+            }
+
+            // Shelf is gone, head back
+            let Some((target_shelf, shelf_transform)) = employee.target_shelf.and_then(|shelf| {
+                shelf_query
+                    .get(shelf)
+                    .ok()
+                    .map(|transform| (shelf, transform))
+            }) else {
+                *status = ActionStatus::Failure;
+                return;
+            };
+
+            // Start restocking once the shelf has been reached
+            if employee.current_shelf == Some(target_shelf) {
+                *status = ActionStatus::Success;
+                return;
+            }
+
+            // Shelves can only be restocked from their long sides
+            let access_point =
+                Shelf::access_point(shelf_transform, employee_transform.translation.truncate());
+            follow_path(
+                &nav_grid,
+                &mut employee_transform,
+                &mut employee_impulse,
+                &mut nav_path,
+                steering,
+                target_shelf,
+                access_point,
+            );
+        },
+    );
+}
+
+fn employee_restocking(
+    time: Res<Time>,
+    mut employee_query: Query<(&mut Restocking, &mut ActionStatus, &Employee)>,
+    mut shelf_query: Query<&mut Shelf>,
+) {
+    for (mut restocking, mut status, employee) in employee_query.iter_mut() {
+        if !status.is_running() {
+            continue;
+        }
+
+        let Some(mut shelf) = employee
+            .target_shelf
+            .and_then(|shelf| shelf_query.get_mut(shelf).ok())
+        else {
+            *status = ActionStatus::Failure;
+            continue;
+        };
+
+        if restocking.timer.tick(time.delta()).just_finished() && shelf.stock < shelf.capacity {
+            shelf.stock += 1;
+        }
+
+        // Head back for more stock once the shelf is full
+        if shelf.stock >= shelf.capacity {
+            *status = ActionStatus::Success;
+        }
+    }
+}
+
+fn employee_returning(
+    nav_grid: Res<NavGrid>,
+    mut employee_query: Query<
+        (
+            &mut Returning,
+            &mut ActionStatus,
+            &mut Transform,
+            &mut ExternalImpulse,
+            &mut NavPath,
+            &Steering,
+        ),
+        With<Employee>,
+    >,
+    stockroom_query: Query<(Entity, &Transform), (With<Stockroom>, Without<Employee>)>,
+) {
+    employee_query.par_iter_mut().for_each(
+        |(
+            mut returning,
+            mut status,
+            mut employee_transform,
+            mut employee_impulse,
+            mut nav_path,
+            steering,
+        )| {
+            if !status.is_running() {
+                return;
+            }
+
+            let employee_position = employee_transform.translation.truncate();
+            let stockroom = match returning.stockroom {
+                Some(stockroom) => stockroom,
+                None => {
+                    let closest_stockroom = stockroom_query
+                        .iter()
+                        .min_by(|(_, a), (_, b)| {
+                            let a_distance =
+                                employee_position.distance_squared(a.translation.truncate());
+                            let b_distance =
+                                employee_position.distance_squared(b.translation.truncate());
+                            a_distance.total_cmp(&b_distance)
+                        })
+                        .map(|(stockroom, _)| stockroom);
+                    // Nowhere to go back to, idle in place
+                    let Some(stockroom) = closest_stockroom else {
+                        *status = ActionStatus::Failure;
+                        return;
+                    };
+
+                    returning.stockroom = Some(stockroom);
+                    stockroom
+                }
+            };
+
+            let Ok((_, stockroom_transform)) = stockroom_query.get(stockroom) else {
+                // Stockroom is gone, find another one
+                returning.stockroom = None;
+                return;
+            };
+            let stockroom_position = stockroom_transform.translation.truncate();
+            if employee_position.distance(stockroom_position) <= STOCKROOM_RADIUS {
+                *status = ActionStatus::Success;
+                return;
+            }
+
+            follow_path(
+                &nav_grid,
+                &mut employee_transform,
+                &mut employee_impulse,
+                &mut nav_path,
+                steering,
+                stockroom,
+                stockroom_position,
+            );
+        },
+    );
+}
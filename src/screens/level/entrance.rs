@@ -0,0 +1,142 @@
+use crate::{
+    GameAssets,
+    screens::{
+        Screen,
+        level::{
+            GameTimer, LevelState,
+            rng::GameRng,
+            shopper::{Shopper, SpawnShopper},
+        },
+    },
+};
+use bevy::{color::palettes::css::*, prelude::*};
+use serde::Deserialize;
+
+/// Entrances stop letting shoppers in once the store holds this many.
+const MAX_SHOPPERS: usize = 40;
+/// How far from the middle of an entrance new shoppers can appear.
+const ENTRANCE_SPREAD: f32 = 40.0;
+
+/// Shoppers let in per minute over the course of a round, as `(remaining seconds, rate)`
+/// keyframes counting down to the end of the round.
+///
+/// Keyed off the time left so the difficulty's longer or shorter time limits keep a late rush
+/// late. The rate is linearly interpolated between keyframes, and two keyframes at the same time
+/// make a sudden jump, e.g. a rush once the last minute starts.
+#[derive(Clone, Default, Reflect, Deserialize)]
+pub struct SpawnCurve(Vec<(f32, f32)>);
+
+impl SpawnCurve {
+    fn rate_at(&self, remaining: f32) -> f32 {
+        match self.0.iter().position(|(time, _)| *time < remaining) {
+            None => self.0.last().map_or(0.0, |(_, rate)| *rate),
+            Some(0) => self.0[0].1,
+            Some(next) => {
+                let (start_time, start_rate) = self.0[next - 1];
+                let (end_time, end_rate) = self.0[next];
+                let t = (start_time - remaining) / (start_time - end_time);
+                start_rate + (end_rate - start_rate) * t
+            }
+        }
+    }
+}
+
+#[derive(Event, Clone, Deserialize)]
+pub struct SpawnEntrance {
+    pub position: Vec2,
+    pub spawn_curve: SpawnCurve,
+}
+
+/// Where new shoppers come into the store.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Entrance {
+    spawn_curve: SpawnCurve,
+    /// Fraction of a shopper waiting to come in, spawned once it reaches one.
+    pending: f32,
+}
+
+pub fn plugin(app: &mut App) {
+    // Register necessary types
+    app.register_type::<Entrance>();
+
+    // Register spawn events
+    app.add_event::<SpawnEntrance>();
+
+    // Entrance systems
+    app.add_systems(
+        FixedUpdate,
+        (
+            spawn_entrances.run_if(on_event::<SpawnEntrance>),
+            entrance_letting_in.run_if(in_state(LevelState::Playing)),
+        )
+            .run_if(in_state(Screen::Level)),
+    );
+    app.add_systems(OnExit(Screen::Level), despawn_entrances);
+}
+
+fn spawn_entrances(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    mut events: EventReader<SpawnEntrance>,
+) {
+    let entrance_size = Vec2::splat(2.0 * ENTRANCE_SPREAD + 40.0);
+
+    for event in events.read() {
+        commands.spawn((
+            Name::new("Entrance"),
+            Entrance {
+                spawn_curve: event.spawn_curve.clone(),
+                pending: 0.0,
+            },
+            Sprite::from_color(STEEL_BLUE.with_alpha(0.6), entrance_size),
+            Transform::from_translation(event.position.extend(-1.0)),
+            Text2d::new("Entrance"),
+            TextFont {
+                font: assets.game_font.clone(),
+                font_size: 20.0,
+                ..Default::default()
+            },
+        ));
+    }
+}
+
+fn despawn_entrances(mut commands: Commands, query: Query<Entity, With<Entrance>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn entrance_letting_in(
+    time: Res<Time>,
+    game_timer: Res<GameTimer>,
+    mut entrance_query: Query<(&Transform, &mut Entrance)>,
+    shopper_query: Query<(), With<Shopper>>,
+    mut game_rng: ResMut<GameRng>,
+    mut shopper_events: EventWriter<SpawnShopper>,
+) {
+    let remaining = game_timer.0.remaining_secs();
+    let mut shopper_count = shopper_query.iter().count();
+
+    for (entrance_transform, mut entrance) in entrance_query.iter_mut() {
+        let rate = entrance.spawn_curve.rate_at(remaining);
+        entrance.pending += rate / 60.0 * time.delta_secs();
+
+        while entrance.pending >= 1.0 {
+            entrance.pending -= 1.0;
+
+            // Store is packed, turn them away
+            if shopper_count >= MAX_SHOPPERS {
+                continue;
+            }
+
+            let offset =
+                Vec2::new(game_rng.f32() - 0.5, game_rng.f32() - 0.5) * 2.0 * ENTRANCE_SPREAD;
+            shopper_events.write(SpawnShopper {
+                position: entrance_transform.translation.truncate() + offset,
+                archetype: None,
+            });
+            shopper_count += 1;
+        }
+    }
+}
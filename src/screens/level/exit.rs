@@ -0,0 +1,58 @@
+use crate::{GameAssets, screens::Screen};
+use bevy::{color::palettes::css::*, prelude::*};
+use serde::Deserialize;
+
+/// How close a shopper needs to get to an exit to leave the store.
+pub const EXIT_RADIUS: f32 = 60.0;
+
+#[derive(Event, Clone, Deserialize)]
+pub struct SpawnExit {
+    pub position: Vec2,
+}
+
+/// Where shoppers leave the store once they've paid.
+#[derive(Component)]
+pub struct Exit;
+
+pub fn plugin(app: &mut App) {
+    // Register spawn events
+    app.add_event::<SpawnExit>();
+
+    // Exit systems
+    app.add_systems(
+        FixedUpdate,
+        spawn_exits
+            .run_if(on_event::<SpawnExit>)
+            .run_if(in_state(Screen::Level)),
+    );
+    app.add_systems(OnExit(Screen::Level), despawn_exits);
+}
+
+fn spawn_exits(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    mut events: EventReader<SpawnExit>,
+) {
+    let exit_size = Vec2::splat(2.0 * EXIT_RADIUS);
+
+    for event in events.read() {
+        commands.spawn((
+            Name::new("Exit"),
+            Exit,
+            Sprite::from_color(FOREST_GREEN.with_alpha(0.6), exit_size),
+            Transform::from_translation(event.position.extend(-1.0)),
+            Text2d::new("Exit"),
+            TextFont {
+                font: assets.game_font.clone(),
+                font_size: 20.0,
+                ..Default::default()
+            },
+        ));
+    }
+}
+
+fn despawn_exits(mut commands: Commands, query: Query<Entity, With<Exit>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
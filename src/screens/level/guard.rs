@@ -0,0 +1,346 @@
+use std::time::Duration;
+
+use crate::screens::{
+    LossReason, Screen,
+    level::{
+        GameLayer, GameTimer, LevelState,
+        behavior::{ActionStatus, Behavior, BehaviorTree},
+        navigation::{NavGrid, NavPath, WAYPOINT_RADIUS},
+        player::Player,
+        round::{RoundEnded, RoundOutcome},
+        shopper::Shopper,
+        steering::Steering,
+    },
+};
+use avian2d::prelude::*;
+use bevy::{color::palettes::css::*, prelude::*};
+use serde::Deserialize;
+
+/// Number of shoppers that have to be fleeing or stampeding at once for security to step in.
+const PANICKED_SHOPPERS_FOR_ALARM: usize = 4;
+const GUARD_SPEED: f32 = 150.0;
+/// Seconds before a guard gives up on a chase.
+const CHASE_DURATION: f32 = 20.0;
+/// Seconds between replanning the path to the player during a chase.
+const CHASE_REPLAN_INTERVAL: f32 = 0.5;
+/// Seconds a guard ignores alarms after a chase ends.
+const ALARM_COOLDOWN: f32 = 15.0;
+
+/// What happens when a guard catches the player.
+#[derive(Clone, Copy, Default, Reflect, Deserialize)]
+pub enum CatchPenalty {
+    /// The round is lost on the spot.
+    #[default]
+    EndRound,
+    /// Seconds taken off the round timer.
+    TimePenalty(f32),
+}
+
+#[derive(Event, Clone, Deserialize)]
+pub struct SpawnGuard {
+    pub position: Vec2,
+    /// Points the guard walks between while nothing is going on, in order.
+    #[serde(default)]
+    pub patrol: Vec<Vec2>,
+    #[serde(default)]
+    pub on_catch: CatchPenalty,
+}
+
+/// Triggered when the player gives security a reason to step in.
+#[derive(Event, Clone, Copy, Debug)]
+pub enum SecurityAlarm {
+    /// Too many shoppers are panicking at once.
+    Panic,
+    /// The player went past the checkouts without paying.
+    Shoplifting,
+}
+
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Guard {
+    patrol: Vec<Vec2>,
+    patrol_index: usize,
+    on_catch: CatchPenalty,
+    /// Alarms are ignored until this finishes.
+    cooldown: Timer,
+    /// Whether the guard is responding to an alarm.
+    alerted: bool,
+}
+
+impl Guard {
+    /// Whether the guard is free to respond to an alarm.
+    fn available(&self) -> bool {
+        self.cooldown.finished() && !self.alerted
+    }
+}
+
+/// Patrols until an alarm goes off, then chases the player.
+fn guard_behavior() -> Behavior {
+    Behavior::repeat(Behavior::Selector(vec![
+        Behavior::guard(
+            "Alerted",
+            |guard: &Guard| guard.alerted,
+            Behavior::action(Chasing::default()),
+        ),
+        Behavior::action(Patrolling),
+    ]))
+}
+
+// Guard actions, run by the guard's behavior tree
+
+/// Walking the patrol route, for as long as nothing is going on.
+#[derive(Component, Reflect, Clone, Default)]
+#[reflect(Component)]
+struct Patrolling;
+
+/// Going after the player, until caught or given up on.
+#[derive(Component, Reflect, Clone)]
+#[reflect(Component)]
+struct Chasing {
+    timer: Timer,
+    replan_timer: Timer,
+}
+
+impl Default for Chasing {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(CHASE_DURATION, TimerMode::Once),
+            replan_timer: Timer::from_seconds(CHASE_REPLAN_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+pub fn plugin(app: &mut App) {
+    // Register necessary types
+    app.register_type::<Guard>();
+    app.register_type::<Patrolling>();
+    app.register_type::<Chasing>();
+
+    // Register spawn events
+    app.add_event::<SpawnGuard>();
+
+    // Guard systems
+    app.add_systems(
+        FixedUpdate,
+        (
+            spawn_guards.run_if(on_event::<SpawnGuard>),
+            (panic_alarm, guard_cooldown, guard_patrolling, guard_chasing)
+                .run_if(in_state(LevelState::Playing)),
+        )
+            .run_if(in_state(Screen::Level)),
+    );
+    app.add_systems(OnExit(Screen::Level), despawn_guards);
+
+    // Add observers
+    app.add_observer(guards_respond_to_alarm);
+}
+
+fn spawn_guards(mut commands: Commands, mut events: EventReader<SpawnGuard>) {
+    let guard_size = Vec2::new(72.0, 36.0);
+
+    for event in events.read() {
+        let mut cooldown = Timer::from_seconds(ALARM_COOLDOWN, TimerMode::Once);
+        cooldown.finish();
+
+        commands
+            .spawn((
+                Name::new("Security Guard"),
+                Guard {
+                    patrol: event.patrol.clone(),
+                    patrol_index: 0,
+                    on_catch: event.on_catch,
+                    cooldown,
+                    alerted: false,
+                },
+                BehaviorTree::new(guard_behavior()),
+                NavPath::default(),
+                Steering::default(),
+                Sprite::from_color(NAVY, guard_size),
+                Transform::from_translation(event.position.extend(0.0)),
+                RigidBody::Dynamic,
+                Collider::rectangle(guard_size.x, guard_size.y),
+                CollisionLayers::new(
+                    GameLayer::NPC,
+                    [
+                        GameLayer::Default,
+                        GameLayer::Player,
+                        GameLayer::NPC,
+                        GameLayer::Environment,
+                    ],
+                ),
+                CollisionEventsEnabled,
+                LinearDamping(1.2),
+                AngularDamping(2.0),
+                MaxLinearSpeed(GUARD_SPEED),
+                ExternalImpulse::default().with_persistence(false),
+            ))
+            .observe(guard_caught_player);
+    }
+}
+
+fn despawn_guards(mut commands: Commands, query: Query<Entity, With<Guard>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Calls security once enough shoppers are panicking at the same time.
+fn panic_alarm(mut commands: Commands, shopper_query: Query<&Shopper>, guard_query: Query<&Guard>) {
+    // Don't keep calling while nobody is free to respond
+    if !guard_query.iter().any(Guard::available) {
+        return;
+    }
+
+    let panicked_shoppers = shopper_query
+        .iter()
+        .filter(|shopper| shopper.panic_tier().is_panicking())
+        .count();
+    if panicked_shoppers >= PANICKED_SHOPPERS_FOR_ALARM {
+        commands.trigger(SecurityAlarm::Panic);
+    }
+}
+
+fn guards_respond_to_alarm(
+    trigger: Trigger<SecurityAlarm>,
+    mut guard_query: Query<(&mut Guard, &mut BehaviorTree)>,
+) {
+    for (mut guard, mut behavior_tree) in guard_query.iter_mut() {
+        if !guard.available() {
+            continue;
+        }
+
+        log::info!("Security responding to {:?}", trigger.event());
+        guard.alerted = true;
+        behavior_tree.interrupt();
+    }
+}
+
+fn guard_cooldown(time: Res<Time>, mut guard_query: Query<&mut Guard>) {
+    guard_query.par_iter_mut().for_each(|mut guard| {
+        guard.cooldown.tick(time.delta());
+    });
+}
+
+fn guard_patrolling(
+    mut guard_query: Query<
+        (
+            &mut Guard,
+            &ActionStatus,
+            &mut Transform,
+            &mut ExternalImpulse,
+            &Steering,
+        ),
+        With<Patrolling>,
+    >,
+) {
+    let patrol_impulse = 600.0;
+
+    guard_query.par_iter_mut().for_each(
+        |(mut guard, status, mut guard_transform, mut guard_impulse, steering)| {
+            if !status.is_running() || guard.patrol.is_empty() {
+                return;
+            }
+
+            let position = guard_transform.translation.truncate();
+            let waypoint = guard.patrol[guard.patrol_index % guard.patrol.len()];
+            if position.distance(waypoint) <= WAYPOINT_RADIUS {
+                guard.patrol_index = (guard.patrol_index + 1) % guard.patrol.len();
+                return;
+            }
+
+            let direction = steering.steer((waypoint - position).normalize_or_zero());
+            guard_transform.rotation = Quat::from_rotation_z(direction.to_angle());
+            guard_impulse.apply_impulse(direction * patrol_impulse);
+        },
+    );
+}
+
+fn guard_chasing(
+    time: Res<Time>,
+    nav_grid: Res<NavGrid>,
+    mut guard_query: Query<(
+        &mut Chasing,
+        &mut ActionStatus,
+        &mut Guard,
+        &mut Transform,
+        &mut ExternalImpulse,
+        &mut NavPath,
+        &Steering,
+    )>,
+    player_query: Single<(Entity, &Transform), (With<Player>, Without<Guard>)>,
+) {
+    let chase_impulse = 1200.0;
+    let (player_entity, player_transform) = *player_query;
+    let player_position = player_transform.translation.truncate();
+
+    guard_query.par_iter_mut().for_each(
+        |(
+            mut chasing,
+            mut status,
+            mut guard,
+            mut guard_transform,
+            mut guard_impulse,
+            mut nav_path,
+            steering,
+        )| {
+            if !status.is_running() {
+                return;
+            }
+
+            if chasing.timer.tick(time.delta()).just_finished() {
+                // Lost them, back to the rounds
+                guard.alerted = false;
+                guard.cooldown.reset();
+                *status = ActionStatus::Failure;
+                return;
+            }
+
+            // The player keeps moving, so the path goes stale quickly
+            if chasing.is_added() || chasing.replan_timer.tick(time.delta()).just_finished() {
+                nav_path.clear();
+            }
+
+            let position = guard_transform.translation.truncate();
+            nav_path.update(&nav_grid, position, player_entity, player_position);
+
+            if let Some(waypoint) = nav_path.next_waypoint(position) {
+                let direction = steering.steer((waypoint - position).normalize_or_zero());
+                guard_transform.rotation = Quat::from_rotation_z(direction.to_angle());
+                guard_impulse.apply_impulse(direction * chase_impulse);
+            }
+        },
+    );
+}
+
+fn guard_caught_player(
+    trigger: Trigger<OnCollisionStart>,
+    mut guard_query: Query<(&mut Guard, &mut BehaviorTree), With<Chasing>>,
+    player_query: Query<(), With<Player>>,
+    mut game_timer: ResMut<GameTimer>,
+    mut round_events: EventWriter<RoundEnded>,
+) {
+    if !player_query.contains(trigger.collider) {
+        return;
+    }
+    // Only counts while chasing
+    let Ok((mut guard, mut behavior_tree)) = guard_query.get_mut(trigger.target()) else {
+        return;
+    };
+
+    log::info!("Security caught the player");
+    guard.alerted = false;
+    guard.cooldown.reset();
+    behavior_tree.interrupt();
+
+    let round_over = match guard.on_catch {
+        CatchPenalty::EndRound => true,
+        CatchPenalty::TimePenalty(seconds) => {
+            game_timer.0.tick(Duration::from_secs_f32(seconds.max(0.0)));
+            game_timer.0.finished()
+        }
+    };
+
+    if round_over {
+        round_events.write(RoundEnded(RoundOutcome::Lost(LossReason::CaughtBySecurity)));
+    }
+}
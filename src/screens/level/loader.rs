@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use crate::screens::level::{
+    Item,
+    checkout::{SpawnCheckoutArea, SpawnCheckoutCounter},
+    employee::{SpawnEmployee, SpawnStockroom},
+    entrance::SpawnEntrance,
+    exit::SpawnExit,
+    guard::SpawnGuard,
+    player::SpawnPlayer,
+    shelf::SpawnShelf,
+    shopper::{PanicContagion, ShopperArchetype, SpawnShopper},
+};
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    prelude::*,
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+/// A store layout loaded from a `.level.ron` file.
+///
+/// Every entry is fed into the matching spawn event when the level is entered, so designers can
+/// add or tweak stores without recompiling.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct LevelData {
+    /// Round time limit, in seconds.
+    pub time_limit: f32,
+    /// Seed for the round's randomness, a fresh one is rolled every round if not given.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    pub objectives: HashMap<Item, u32>,
+    #[serde(default)]
+    pub panic_contagion: PanicContagion,
+    /// Kinds of shoppers that turn up in this store.
+    #[serde(default)]
+    pub shopper_archetypes: Vec<ShopperArchetype>,
+    pub player: SpawnPlayer,
+    #[serde(default)]
+    pub shelves: Vec<SpawnShelf>,
+    #[serde(default)]
+    pub shoppers: Vec<SpawnShopper>,
+    #[serde(default)]
+    pub checkout_counters: Vec<SpawnCheckoutCounter>,
+    /// Areas past the checkouts, entering one with unpaid items calls security.
+    #[serde(default)]
+    pub checkout_areas: Vec<SpawnCheckoutArea>,
+    #[serde(default)]
+    pub stockrooms: Vec<SpawnStockroom>,
+    #[serde(default)]
+    pub employees: Vec<SpawnEmployee>,
+    #[serde(default)]
+    pub entrances: Vec<SpawnEntrance>,
+    #[serde(default)]
+    pub exits: Vec<SpawnExit>,
+    #[serde(default)]
+    pub guards: Vec<SpawnGuard>,
+}
+
+#[derive(Default)]
+pub struct LevelDataLoader;
+
+#[derive(Error, Debug)]
+pub enum LevelDataLoaderError {
+    #[error("Could not read level file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse level file: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for LevelDataLoader {
+    type Asset = LevelData;
+    type Settings = ();
+    type Error = LevelDataLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &Self::Settings,
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let level = ron::de::from_bytes::<LevelData>(&bytes)?;
+        Ok(level)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
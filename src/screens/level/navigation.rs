@@ -0,0 +1,375 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, VecDeque},
+};
+
+use crate::screens::{Screen, level::steering::Steering};
+use avian2d::prelude::*;
+use bevy::prelude::*;
+
+/// Size of a single navigation grid cell.
+const CELL_SIZE: f32 = 40.0;
+/// Obstacles are inflated by this much so paths keep NPCs clear of corners.
+const AGENT_RADIUS: f32 = 30.0;
+/// Walkable space kept around the outermost obstacles.
+const GRID_MARGIN: f32 = 600.0;
+/// Distance at which a path follower moves on to its next waypoint.
+pub const WAYPOINT_RADIUS: f32 = 30.0;
+
+/// Static geometry that NPCs have to path around.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct NavObstacle {
+    pub size: Vec2,
+}
+
+/// Walkability grid built from every [`NavObstacle`] in the level.
+#[derive(Resource, Default)]
+pub struct NavGrid {
+    origin: Vec2,
+    width: usize,
+    height: usize,
+    blocked: Vec<bool>,
+    /// Bumped on every rebuild so followers know to replan.
+    pub version: u32,
+}
+
+/// A path being followed by an NPC.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct NavPath {
+    /// Entity the path leads to.
+    pub goal: Option<Entity>,
+    pub waypoints: Vec<Vec2>,
+    pub grid_version: u32,
+}
+
+impl NavPath {
+    /// Replans towards `goal` if it changed or the grid was rebuilt since the last plan.
+    pub fn update(&mut self, grid: &NavGrid, from: Vec2, goal: Entity, goal_position: Vec2) {
+        if self.goal == Some(goal) && self.grid_version == grid.version {
+            return;
+        }
+
+        self.goal = Some(goal);
+        self.grid_version = grid.version;
+        self.waypoints = grid
+            .find_path(from, goal_position)
+            .unwrap_or_else(|| vec![goal_position]);
+    }
+
+    /// Point to steer towards, dropping waypoints that have already been reached.
+    pub fn next_waypoint(&mut self, position: Vec2) -> Option<Vec2> {
+        while self.waypoints.len() > 1 && self.waypoints[0].distance(position) <= WAYPOINT_RADIUS {
+            self.waypoints.remove(0);
+        }
+        self.waypoints.first().copied()
+    }
+
+    pub fn clear(&mut self) {
+        self.goal = None;
+        self.waypoints.clear();
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+struct OpenCell {
+    cost: f32,
+    index: usize,
+}
+
+impl Eq for OpenCell {}
+
+impl Ord for OpenCell {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so the heap pops the cheapest cell first
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+impl PartialOrd for OpenCell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl NavGrid {
+    fn cell_of(&self, position: Vec2) -> Option<IVec2> {
+        let cell = ((position - self.origin) / CELL_SIZE).floor().as_ivec2();
+        self.in_bounds(cell).then_some(cell)
+    }
+
+    fn in_bounds(&self, cell: IVec2) -> bool {
+        cell.x >= 0
+            && cell.y >= 0
+            && (cell.x as usize) < self.width
+            && (cell.y as usize) < self.height
+    }
+
+    fn index_of(&self, cell: IVec2) -> usize {
+        cell.y as usize * self.width + cell.x as usize
+    }
+
+    fn cell_at(&self, index: usize) -> IVec2 {
+        IVec2::new((index % self.width) as i32, (index / self.width) as i32)
+    }
+
+    fn center_of(&self, cell: IVec2) -> Vec2 {
+        self.origin + (cell.as_vec2() + 0.5) * CELL_SIZE
+    }
+
+    fn is_walkable(&self, cell: IVec2) -> bool {
+        self.in_bounds(cell) && !self.blocked[self.index_of(cell)]
+    }
+
+    /// Closest walkable cell to `cell`, preferring ones nearer to `towards` on ties.
+    fn nearest_walkable(&self, cell: IVec2, towards: Vec2) -> Option<IVec2> {
+        if self.is_walkable(cell) {
+            return Some(cell);
+        }
+
+        let mut visited = vec![false; self.blocked.len()];
+        let mut frontier = VecDeque::from([cell]);
+        visited[self.index_of(cell)] = true;
+
+        while !frontier.is_empty() {
+            let mut found = Vec::new();
+            for _ in 0..frontier.len() {
+                let current = frontier.pop_front().unwrap();
+                for offset in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+                    let neighbor = current + offset;
+                    if !self.in_bounds(neighbor) || visited[self.index_of(neighbor)] {
+                        continue;
+                    }
+                    visited[self.index_of(neighbor)] = true;
+
+                    if self.is_walkable(neighbor) {
+                        found.push(neighbor);
+                    } else {
+                        frontier.push_back(neighbor);
+                    }
+                }
+            }
+
+            if let Some(closest) = found.into_iter().min_by(|a, b| {
+                let a_distance = self.center_of(*a).distance_squared(towards);
+                let b_distance = self.center_of(*b).distance_squared(towards);
+                a_distance.total_cmp(&b_distance)
+            }) {
+                return Some(closest);
+            }
+        }
+
+        None
+    }
+
+    /// Whether a straight walk from `from` to `to` stays on walkable cells.
+    fn line_of_sight(&self, from: Vec2, to: Vec2) -> bool {
+        let steps = (from.distance(to) / (CELL_SIZE / 2.0)).ceil().max(1.0) as usize;
+        (0..=steps).all(|step| {
+            let point = from.lerp(to, step as f32 / steps as f32);
+            self.cell_of(point)
+                .is_some_and(|cell| self.is_walkable(cell))
+        })
+    }
+
+    /// A* over the grid, returning smoothed waypoints from `from` to `to`.
+    ///
+    /// Returns `None` if either end is off the grid or no path exists.
+    pub fn find_path(&self, from: Vec2, to: Vec2) -> Option<Vec<Vec2>> {
+        let start = self.nearest_walkable(self.cell_of(from)?, from)?;
+        let goal = self.nearest_walkable(self.cell_of(to)?, from)?;
+        let start_index = self.index_of(start);
+        let goal_index = self.index_of(goal);
+
+        let heuristic = |cell: IVec2| {
+            let delta = (cell - goal).abs();
+            let (min, max) = (delta.x.min(delta.y) as f32, delta.x.max(delta.y) as f32);
+            (max - min) + min * std::f32::consts::SQRT_2
+        };
+
+        let mut came_from = vec![usize::MAX; self.blocked.len()];
+        let mut cost_so_far = vec![f32::INFINITY; self.blocked.len()];
+        let mut open = BinaryHeap::from([OpenCell {
+            cost: heuristic(start),
+            index: start_index,
+        }]);
+        cost_so_far[start_index] = 0.0;
+
+        while let Some(OpenCell { index, .. }) = open.pop() {
+            if index == goal_index {
+                break;
+            }
+
+            let cell = self.cell_at(index);
+            for offset in [
+                IVec2::new(1, 0),
+                IVec2::new(-1, 0),
+                IVec2::new(0, 1),
+                IVec2::new(0, -1),
+                IVec2::new(1, 1),
+                IVec2::new(1, -1),
+                IVec2::new(-1, 1),
+                IVec2::new(-1, -1),
+            ] {
+                let neighbor = cell + offset;
+                if !self.is_walkable(neighbor) {
+                    continue;
+                }
+
+                // Don't cut corners past obstacles
+                let diagonal = offset.x != 0 && offset.y != 0;
+                if diagonal
+                    && (!self.is_walkable(cell + IVec2::new(offset.x, 0))
+                        || !self.is_walkable(cell + IVec2::new(0, offset.y)))
+                {
+                    continue;
+                }
+
+                let neighbor_index = self.index_of(neighbor);
+                let step_cost = if diagonal {
+                    std::f32::consts::SQRT_2
+                } else {
+                    1.0
+                };
+                let new_cost = cost_so_far[index] + step_cost;
+                if new_cost < cost_so_far[neighbor_index] {
+                    cost_so_far[neighbor_index] = new_cost;
+                    came_from[neighbor_index] = index;
+                    open.push(OpenCell {
+                        cost: new_cost + heuristic(neighbor),
+                        index: neighbor_index,
+                    });
+                }
+            }
+        }
+
+        if start_index != goal_index && came_from[goal_index] == usize::MAX {
+            return None;
+        }
+
+        // Walk back from the goal
+        let mut cells = vec![goal_index];
+        while let Some(&index) = cells.last() {
+            if index == start_index {
+                break;
+            }
+            cells.push(came_from[index]);
+        }
+        cells.reverse();
+
+        let mut points = cells
+            .into_iter()
+            .map(|index| self.center_of(self.cell_at(index)))
+            .collect::<Vec<_>>();
+        points[0] = from;
+        points.push(to);
+
+        // Skip any waypoints that can be walked past in a straight line
+        let mut waypoints = Vec::new();
+        let mut anchor = from;
+        for i in 1..points.len() {
+            let is_last = i + 1 == points.len();
+            if is_last || !self.line_of_sight(anchor, points[i + 1]) {
+                waypoints.push(points[i]);
+                anchor = points[i];
+            }
+        }
+
+        Some(waypoints)
+    }
+}
+
+/// Steers an NPC along its path around the shelves towards `target`.
+pub fn follow_path(
+    nav_grid: &NavGrid,
+    transform: &mut Transform,
+    impulse: &mut ExternalImpulse,
+    nav_path: &mut NavPath,
+    steering: &Steering,
+    target: Entity,
+    target_position: Vec2,
+) {
+    let travel_impulse = 1000.0;
+
+    let position = transform.translation.truncate();
+    nav_path.update(nav_grid, position, target, target_position);
+
+    if let Some(waypoint) = nav_path.next_waypoint(position) {
+        let direction = steering.steer((waypoint - position).normalize_or_zero());
+
+        transform.rotation = Quat::from_rotation_z(direction.to_angle());
+        impulse.apply_impulse(direction * travel_impulse);
+    }
+}
+
+pub fn plugin(app: &mut App) {
+    // Register necessary types
+    app.register_type::<NavObstacle>();
+    app.register_type::<NavPath>();
+
+    // Add resources
+    app.init_resource::<NavGrid>();
+
+    // Navigation systems
+    app.add_systems(
+        FixedUpdate,
+        rebuild_nav_grid.run_if(in_state(Screen::Level)),
+    );
+}
+
+/// Rebuilds the grid whenever obstacles are added or removed, e.g. when the level is spawned or
+/// a shelf is placed at runtime.
+fn rebuild_nav_grid(
+    mut grid: ResMut<NavGrid>,
+    obstacle_query: Query<(&Transform, &NavObstacle)>,
+    added_query: Query<(), Added<NavObstacle>>,
+    mut removed: RemovedComponents<NavObstacle>,
+) {
+    let removed_any = removed.read().count() > 0;
+    if added_query.is_empty() && !removed_any {
+        return;
+    }
+
+    // World space bounds of every obstacle, inflated by the agent radius
+    let obstacles = obstacle_query
+        .iter()
+        .map(|(transform, obstacle)| {
+            let angle = transform.rotation.to_euler(EulerRot::XYZ).2;
+            let (sin, cos) = angle.sin_cos();
+            let half = obstacle.size / 2.0;
+            let half_size = Vec2::new(
+                cos.abs() * half.x + sin.abs() * half.y,
+                sin.abs() * half.x + cos.abs() * half.y,
+            ) + AGENT_RADIUS;
+            Rect::from_center_half_size(transform.translation.truncate(), half_size)
+        })
+        .collect::<Vec<_>>();
+
+    let Some(bounds) = obstacles.iter().copied().reduce(|a, b| a.union(b)) else {
+        *grid = NavGrid {
+            version: grid.version + 1,
+            ..Default::default()
+        };
+        return;
+    };
+    let bounds = bounds.inflate(GRID_MARGIN);
+
+    let width = (bounds.width() / CELL_SIZE).ceil() as usize;
+    let height = (bounds.height() / CELL_SIZE).ceil() as usize;
+    let mut new_grid = NavGrid {
+        origin: bounds.min,
+        width,
+        height,
+        blocked: vec![false; width * height],
+        version: grid.version + 1,
+    };
+
+    for index in 0..new_grid.blocked.len() {
+        let center = new_grid.center_of(new_grid.cell_at(index));
+        new_grid.blocked[index] = obstacles.iter().any(|rect| rect.contains(center));
+    }
+
+    log::debug!("Rebuilt navigation grid ({} x {} cells)", width, height);
+    *grid = new_grid;
+}
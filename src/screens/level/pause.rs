@@ -0,0 +1,240 @@
+use crate::{
+    GameAssets,
+    screens::{
+        Screen,
+        level::LevelState,
+        settings::{SettingsBackButton, despawn_settings_menu, spawn_settings_menu},
+    },
+    ui::{ButtonClicked, button},
+};
+use avian2d::prelude::*;
+use bevy::{color::palettes::css::*, prelude::*};
+use bevy_enhanced_input::prelude::*;
+
+#[derive(InputContext)]
+struct PauseInputContext;
+
+#[derive(Debug, InputAction)]
+#[input_action(output = bool)]
+struct TogglePause;
+
+/// Exists while gameplay is frozen, whether in the pause menu or the settings opened from it.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct TimeFrozen;
+
+impl ComputedStates for TimeFrozen {
+    type SourceStates = LevelState;
+
+    fn compute(level_state: LevelState) -> Option<Self> {
+        matches!(level_state, LevelState::Paused | LevelState::Settings).then_some(TimeFrozen)
+    }
+}
+
+#[derive(Component)]
+struct PauseUI;
+
+#[derive(Component)]
+struct ResumeButton;
+
+#[derive(Component)]
+struct RestartButton;
+
+#[derive(Component)]
+struct SettingsButton;
+
+#[derive(Component)]
+struct QuitButton;
+
+pub fn plugin(app: &mut App) {
+    app.add_input_context::<PauseInputContext>();
+    app.add_computed_state::<TimeFrozen>();
+
+    // Pause input, only available during a round
+    app.add_systems(OnEnter(Screen::Level), spawn_pause_actions);
+    app.add_systems(OnExit(Screen::Level), despawn_pause_actions);
+
+    // Pause systems
+    app.add_systems(OnEnter(TimeFrozen), pause_time);
+    app.add_systems(OnExit(TimeFrozen), unpause_time);
+    app.add_systems(OnEnter(LevelState::Paused), spawn_pause_menu);
+    app.add_systems(OnExit(LevelState::Paused), despawn_pause_menu);
+    app.add_systems(OnEnter(LevelState::Settings), spawn_settings_menu);
+    app.add_systems(OnExit(LevelState::Settings), despawn_settings_menu);
+    app.add_systems(OnEnter(Screen::Restart), restart_level);
+
+    // Add observers
+    app.add_observer(toggle_pause);
+    app.add_observer(resume_button_clicked);
+    app.add_observer(restart_button_clicked);
+    app.add_observer(settings_button_clicked);
+    app.add_observer(settings_back_button_clicked);
+    app.add_observer(quit_button_clicked);
+}
+
+fn spawn_pause_actions(mut commands: Commands) {
+    let mut actions = Actions::<PauseInputContext>::default();
+    actions
+        .bind::<TogglePause>()
+        .to((KeyCode::Escape, GamepadButton::Start))
+        .with_conditions(Press::default());
+
+    commands.spawn((Name::new("Pause Input"), actions));
+}
+
+fn despawn_pause_actions(
+    mut commands: Commands,
+    query: Single<Entity, With<Actions<PauseInputContext>>>,
+) {
+    commands.entity(query.entity()).despawn();
+}
+
+fn toggle_pause(
+    _trigger: Trigger<Fired<TogglePause>>,
+    level_state: Res<State<LevelState>>,
+    mut next_level_state: ResMut<NextState<LevelState>>,
+) {
+    match level_state.get() {
+        LevelState::Playing => next_level_state.set(LevelState::Paused),
+        LevelState::Paused => next_level_state.set(LevelState::Playing),
+        LevelState::Settings => next_level_state.set(LevelState::Paused),
+        // Nothing to pause before the round starts or after it's over
+        LevelState::Countdown | LevelState::Ending => {}
+    }
+}
+
+fn pause_time(mut virtual_time: ResMut<Time<Virtual>>, mut physics_time: ResMut<Time<Physics>>) {
+    log::info!("Pausing game");
+    virtual_time.pause();
+    physics_time.pause();
+}
+
+fn unpause_time(mut virtual_time: ResMut<Time<Virtual>>, mut physics_time: ResMut<Time<Physics>>) {
+    log::info!("Resuming game");
+    virtual_time.unpause();
+    physics_time.unpause();
+}
+
+fn spawn_pause_menu(mut commands: Commands, assets: Res<GameAssets>) {
+    commands
+        .spawn((
+            Name::new("Pause UI"),
+            PauseUI,
+            Node {
+                width: Val::Vw(100.0),
+                height: Val::Vh(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            BackgroundColor(Color::BLACK.with_alpha(0.6)),
+            // Draw over the game timer and other level UI
+            GlobalZIndex(1),
+            children![(
+                Name::new("Title"),
+                Text::new("Paused"),
+                TextColor(GHOST_WHITE.into()),
+                TextFont {
+                    font: assets.ui_font.clone(),
+                    font_size: 48.0,
+                    ..Default::default()
+                },
+                TextLayout {
+                    justify: JustifyText::Center,
+                    ..Default::default()
+                },
+            )],
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Name::new("Button Container"),
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        padding: UiRect::vertical(Val::Px(32.0)),
+                        row_gap: Val::Px(12.0),
+                        ..Default::default()
+                    },
+                ))
+                .with_children(|parent| {
+                    parent.spawn((ResumeButton, button("Resume", assets.ui_font.clone(), 32.0)));
+                    parent.spawn((
+                        RestartButton,
+                        button("Restart Level", assets.ui_font.clone(), 32.0),
+                    ));
+                    parent.spawn((
+                        SettingsButton,
+                        button("Settings", assets.ui_font.clone(), 32.0),
+                    ));
+                    parent.spawn((
+                        QuitButton,
+                        button("Quit to Menu", assets.ui_font.clone(), 32.0),
+                    ));
+                });
+        });
+}
+
+fn despawn_pause_menu(mut commands: Commands, query: Single<Entity, With<PauseUI>>) {
+    commands.entity(query.entity()).despawn();
+}
+
+fn resume_button_clicked(
+    trigger: Trigger<ButtonClicked>,
+    mut next_level_state: ResMut<NextState<LevelState>>,
+    query: Query<(), With<ResumeButton>>,
+) {
+    if query.contains(trigger.target()) {
+        next_level_state.set(LevelState::Playing);
+    }
+}
+
+fn restart_button_clicked(
+    trigger: Trigger<ButtonClicked>,
+    mut next_screen: ResMut<NextState<Screen>>,
+    query: Query<(), With<RestartButton>>,
+) {
+    if query.contains(trigger.target()) {
+        log::info!("Restarting level...");
+        next_screen.set(Screen::Restart);
+    }
+}
+
+fn settings_button_clicked(
+    trigger: Trigger<ButtonClicked>,
+    mut next_level_state: ResMut<NextState<LevelState>>,
+    query: Query<(), With<SettingsButton>>,
+) {
+    if query.contains(trigger.target()) {
+        next_level_state.set(LevelState::Settings);
+    }
+}
+
+fn settings_back_button_clicked(
+    trigger: Trigger<ButtonClicked>,
+    level_state: Option<Res<State<LevelState>>>,
+    mut next_level_state: ResMut<NextState<LevelState>>,
+    query: Query<(), With<SettingsBackButton>>,
+) {
+    // Only when the settings were opened from the pause menu, not the main menu
+    let in_level_settings = level_state.is_some_and(|state| *state.get() == LevelState::Settings);
+    if query.contains(trigger.target()) && in_level_settings {
+        next_level_state.set(LevelState::Paused);
+    }
+}
+
+fn quit_button_clicked(
+    trigger: Trigger<ButtonClicked>,
+    mut next_screen: ResMut<NextState<Screen>>,
+    query: Query<(), With<QuitButton>>,
+) {
+    if query.contains(trigger.target()) {
+        log::info!("Quitting to menu...");
+        next_screen.set(Screen::MainMenu);
+    }
+}
+
+/// Leaving [`Screen::Level`] for [`Screen::Restart`] already despawned the round, so go straight
+/// back in to start a fresh one.
+fn restart_level(mut next_screen: ResMut<NextState<Screen>>) {
+    next_screen.set(Screen::Level);
+}
@@ -1,16 +1,31 @@
+use std::collections::HashMap;
+
 use crate::{
     DefaultCamera, GameAssets,
     screens::{
         Screen,
-        level::{GameLayer, Inventory, Item, Objectives, shelf::Shelf},
+        controls::InputBindings,
+        level::{
+            GameLayer, Inventory, Item, LevelState, Objectives, dropped_item::SpawnDroppedItem,
+            rng::GameRng, shelf::Shelf, shopper::Shopper,
+        },
+        settings::{InteractMode, Settings},
     },
 };
 use avian2d::prelude::*;
 use bevy::{color::palettes::css::*, prelude::*};
-use bevy_enhanced_input::{prelude::*, preset::Bidirectional};
+use bevy_enhanced_input::prelude::*;
+use serde::Deserialize;
 
 const LINEAR_ACCELERATION: f32 = 3.0;
 const STEER_ACCELERATION: f32 = 0.1;
+/// Seconds between grabs while interacting continuously.
+const INTERACT_INTERVAL: f32 = 0.25;
+/// Shopper momentum (mass times speed) above which a collision knocks the player down.
+const KNOCKDOWN_MOMENTUM: f32 = 360_000.0;
+const STUN_DURATION: f32 = 1.5;
+/// Most items the player can spill in a single knockdown.
+const MAX_SPILLED_ITEMS: u32 = 3;
 
 #[derive(InputContext)]
 struct PlayerInputContext;
@@ -27,6 +42,11 @@ struct Steer;
 #[input_action(output = bool)]
 struct Interact;
 
+#[derive(Event, Clone, Deserialize)]
+pub struct SpawnPlayer {
+    pub position: Vec2,
+}
+
 #[derive(Component)]
 struct PlayerCamera {
     speed_factor: f32,
@@ -36,11 +56,40 @@ struct PlayerCamera {
 #[reflect(Component)]
 pub struct Player {
     pub current_shelf: Option<Entity>,
+    pub current_counter: Option<Entity>,
+    pub queued_counter: Option<Entity>,
+    /// Items the player has already paid for at a checkout.
+    pub paid_items: HashMap<Item, u32>,
+    /// Whether the player is on the exit side of the checkouts.
+    pub past_checkout: bool,
+}
+
+impl Player {
+    /// Whether the player is carrying anything they haven't paid for.
+    pub fn has_unpaid_items(&self, inventory: &Inventory) -> bool {
+        inventory
+            .0
+            .iter()
+            .any(|(item, count)| *count > self.paid_items.get(item).copied().unwrap_or(0))
+    }
 }
 
 #[derive(Event)]
 pub struct PlayerPickedItem;
 
+/// Added to the player while they're knocked down and can't move or interact.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Stunned {
+    timer: Timer,
+}
+
+/// Added to the player while they're grabbing items in [`InteractMode::Toggle`].
+#[derive(Component)]
+struct Interacting {
+    timer: Timer,
+}
+
 #[derive(Component)]
 struct InventoryUI;
 
@@ -50,23 +99,36 @@ struct InventoryUIContainer;
 pub fn plugin(app: &mut App) {
     // Register necessary types
     app.register_type::<Player>();
+    app.register_type::<Stunned>();
     app.add_input_context::<PlayerInputContext>();
 
+    // Register events
+    app.add_event::<SpawnPlayer>();
+
     // Player systems
     app.add_systems(
         OnEnter(Screen::Level),
-        (spawn_player_camera, spawn_player, spawn_inventory_ui),
+        (spawn_player_camera, spawn_inventory_ui),
+    );
+    app.add_systems(
+        FixedUpdate,
+        spawn_player.run_if(in_state(Screen::Level).and(on_event::<SpawnPlayer>)),
     );
     app.add_systems(
         OnExit(Screen::Level),
         (despawn_player_camera, despawn_player, despawn_inventory_ui),
     );
     app.add_systems(Update, (camera_follow, inventory_changed));
+    app.add_systems(
+        FixedUpdate,
+        (player_stunned, player_interacting).run_if(in_state(LevelState::Playing)),
+    );
 
     // Player input reactions
     app.add_observer(player_acceleration);
     app.add_observer(player_steering);
     app.add_observer(player_interaction);
+    app.add_observer(player_toggle_interacting);
 }
 
 fn spawn_player_camera(
@@ -103,63 +165,87 @@ fn camera_follow(
         * camera_query.1.speed_factor;
 }
 
-fn spawn_player(mut commands: Commands, assets: Res<GameAssets>) {
-    // Bind inputs to actions
+/// Binds the player's actions to their chosen keys and buttons, plus the left stick.
+fn player_actions(bindings: &InputBindings) -> Actions<PlayerInputContext> {
     let mut actions = Actions::<PlayerInputContext>::default();
-    actions
-        .bind::<Accelerate>()
-        .to((
-            Bidirectional {
-                positive: KeyCode::KeyW,
-                negative: KeyCode::KeyS,
-            },
-            Bidirectional {
-                positive: KeyCode::ArrowUp,
-                negative: KeyCode::ArrowDown,
-            },
-            GamepadAxis::LeftStickY,
-        ))
+
+    // Reversing and steering right are the negative ends of their axes
+    let accelerate = actions.bind::<Accelerate>();
+    for binding in &bindings.accelerate_forward {
+        accelerate.to(binding.input());
+    }
+    for binding in &bindings.accelerate_backward {
+        accelerate.to(binding.input().with_modifiers(Negate::all()));
+    }
+    accelerate
+        .to(GamepadAxis::LeftStickY)
         .with_modifiers(DeadZone::default());
-    actions
-        .bind::<Steer>()
-        .to((
-            Bidirectional {
-                positive: KeyCode::KeyA,
-                negative: KeyCode::KeyD,
-            },
-            Bidirectional {
-                positive: KeyCode::ArrowLeft,
-                negative: KeyCode::ArrowRight,
-            },
-            GamepadAxis::LeftStickX.with_modifiers(Negate::all()),
-        ))
+
+    let steer = actions.bind::<Steer>();
+    for binding in &bindings.steer_left {
+        steer.to(binding.input());
+    }
+    for binding in &bindings.steer_right {
+        steer.to(binding.input().with_modifiers(Negate::all()));
+    }
+    steer
+        .to(GamepadAxis::LeftStickX.with_modifiers(Negate::all()))
         .with_modifiers(DeadZone::default());
+
+    let interact = actions.bind::<Interact>();
+    for binding in &bindings.interact {
+        interact.to(binding.input());
+    }
+    // Repeats while held, and starts right away so tapping still works
+    interact.with_conditions(Pulse::new(INTERACT_INTERVAL));
+
     actions
-        .bind::<Interact>()
-        .to((KeyCode::Space, GamepadButton::South))
-        .with_conditions(Press::default());
+}
+
+fn spawn_player(
+    mut commands: Commands,
+    mut events: EventReader<SpawnPlayer>,
+    assets: Res<GameAssets>,
+    bindings: Res<InputBindings>,
+) {
+    let Some(event) = events.read().last() else {
+        return;
+    };
+
+    let actions = player_actions(&bindings);
 
     // Spawn player
     let player_size = Vec2::new(72.0, 36.0);
-    commands.spawn((
-        Name::new("Player"),
-        Player {
-            current_shelf: None,
-        },
-        Inventory::default(),
-        // Sprite::from_color(LIMEGREEN, player_size),
-        Sprite {
-            image: assets.shopper_player.clone(),
-            custom_size: Some(Vec2::new(78.0, 78.0)),
-            ..Default::default()
-        },
-        RigidBody::Dynamic,
-        Collider::rectangle(player_size.x, player_size.y),
-        CollisionLayers::new(GameLayer::Player, [GameLayer::NPC, GameLayer::Environment]),
-        LinearDamping(1.2),
-        AngularDamping(2.0),
-        actions,
-    ));
+    commands
+        .spawn((
+            Name::new("Player"),
+            Player {
+                current_shelf: None,
+                current_counter: None,
+                queued_counter: None,
+                paid_items: HashMap::new(),
+                past_checkout: false,
+            },
+            Inventory::default(),
+            // Sprite::from_color(LIMEGREEN, player_size),
+            Sprite {
+                image: assets.shopper_player.clone(),
+                custom_size: Some(Vec2::new(78.0, 78.0)),
+                ..Default::default()
+            },
+            Transform::from_translation(event.position.extend(0.0)),
+            RigidBody::Dynamic,
+            Collider::rectangle(player_size.x, player_size.y),
+            CollisionLayers::new(
+                GameLayer::Player,
+                [GameLayer::NPC, GameLayer::Environment, GameLayer::Pickup],
+            ),
+            CollisionEventsEnabled,
+            LinearDamping(1.2),
+            AngularDamping(2.0),
+            actions,
+        ))
+        .observe(player_knocked_down);
 }
 
 fn despawn_player(mut commands: Commands, query: Single<Entity, With<Player>>) {
@@ -301,8 +387,14 @@ fn inventory_changed(
 
 fn player_acceleration(
     trigger: Trigger<Fired<Accelerate>>,
-    mut query: Query<(&Transform, &mut LinearVelocity), With<Player>>,
+    level_state: Res<State<LevelState>>,
+    mut query: Query<(&Transform, &mut LinearVelocity), (With<Player>, Without<Stunned>)>,
 ) {
+    // Observers can't have run conditions, so ignore input outside of gameplay
+    if *level_state.get() != LevelState::Playing {
+        return;
+    }
+
     if let Ok((transform, mut linear_velocity)) = query.get_mut(trigger.target()) {
         let angle = transform.rotation.to_euler(EulerRot::XYZ).2;
         let acceleration_mag = LINEAR_ACCELERATION * trigger.value;
@@ -313,8 +405,13 @@ fn player_acceleration(
 
 fn player_steering(
     trigger: Trigger<Fired<Steer>>,
-    mut query: Query<&mut AngularVelocity, With<Player>>,
+    level_state: Res<State<LevelState>>,
+    mut query: Query<&mut AngularVelocity, (With<Player>, Without<Stunned>)>,
 ) {
+    if *level_state.get() != LevelState::Playing {
+        return;
+    }
+
     if let Ok(mut angular_velocity) = query.get_mut(trigger.target()) {
         angular_velocity.0 += trigger.value * STEER_ACCELERATION;
     }
@@ -323,19 +420,186 @@ fn player_steering(
 fn player_interaction(
     trigger: Trigger<Fired<Interact>>,
     mut commands: Commands,
-    mut player_query: Query<(&Player, &mut Inventory)>,
-    shelf_query: Query<&Shelf>,
+    level_state: Res<State<LevelState>>,
+    settings: Res<Settings>,
+    mut player_query: Query<(&mut Player, &mut Inventory), Without<Stunned>>,
+    mut shelf_query: Query<&mut Shelf>,
+) {
+    if *level_state.get() != LevelState::Playing || settings.interact_mode != InteractMode::Hold {
+        return;
+    }
+
+    if let Ok((mut player, mut player_inventory)) = player_query.get_mut(trigger.target()) {
+        interact(
+            &mut commands,
+            &mut player,
+            &mut player_inventory,
+            &mut shelf_query,
+        );
+    }
+}
+
+fn player_toggle_interacting(
+    trigger: Trigger<Started<Interact>>,
+    mut commands: Commands,
+    level_state: Res<State<LevelState>>,
+    settings: Res<Settings>,
+    mut player_query: Query<(&mut Player, &mut Inventory, Has<Interacting>), Without<Stunned>>,
+    mut shelf_query: Query<&mut Shelf>,
+) {
+    if *level_state.get() != LevelState::Playing || settings.interact_mode != InteractMode::Toggle {
+        return;
+    }
+
+    let Ok((mut player, mut player_inventory, interacting)) =
+        player_query.get_mut(trigger.target())
+    else {
+        return;
+    };
+
+    if interacting {
+        commands.entity(trigger.target()).remove::<Interacting>();
+    } else {
+        interact(
+            &mut commands,
+            &mut player,
+            &mut player_inventory,
+            &mut shelf_query,
+        );
+        commands.entity(trigger.target()).insert(Interacting {
+            timer: Timer::from_seconds(INTERACT_INTERVAL, TimerMode::Repeating),
+        });
+    }
+}
+
+fn player_interacting(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<Settings>,
+    mut player_query: Query<
+        (Entity, &mut Player, &mut Inventory, &mut Interacting),
+        Without<Stunned>,
+    >,
+    mut shelf_query: Query<&mut Shelf>,
 ) {
-    if let Ok((player, mut player_inventory)) = player_query.get_mut(trigger.target()) {
-        if let Some(shelf_entity) = player.current_shelf {
-            if let Ok(shelf) = shelf_query.get(shelf_entity) {
-                if let Some(main_item_quantity) = player_inventory.0.get_mut(&shelf.main_item) {
-                    *main_item_quantity = *main_item_quantity + 1;
+    for (entity, mut player, mut inventory, mut interacting) in player_query.iter_mut() {
+        // Switched to holding mid-round
+        if settings.interact_mode != InteractMode::Toggle {
+            commands.entity(entity).remove::<Interacting>();
+            continue;
+        }
+
+        if interacting.timer.tick(time.delta()).just_finished() {
+            interact(&mut commands, &mut player, &mut inventory, &mut shelf_query);
+        }
+    }
+}
+
+/// Takes an item from the shelf the player is at, or joins the checkout line they're next to.
+fn interact(
+    commands: &mut Commands,
+    player: &mut Player,
+    player_inventory: &mut Inventory,
+    shelf_query: &mut Query<&mut Shelf>,
+) {
+    if let Some(shelf_entity) = player.current_shelf {
+        if let Ok(mut shelf) = shelf_query.get_mut(shelf_entity) {
+            if !shelf.take() {
+                log::info!("Shelf {} is out of {}", shelf_entity, shelf.main_item);
+                return;
+            }
+
+            if let Some(main_item_quantity) = player_inventory.0.get_mut(&shelf.main_item) {
+                *main_item_quantity = *main_item_quantity + 1;
+            } else {
+                player_inventory.0.insert(shelf.main_item, 1);
+            }
+            commands.trigger(PlayerPickedItem);
+        }
+    } else if let Some(counter_entity) = player.current_counter {
+        if player.queued_counter.is_none() {
+            log::info!("Player joined the checkout line at {}", counter_entity);
+            player.queued_counter = Some(counter_entity);
+        }
+    }
+}
+
+fn player_knocked_down(
+    trigger: Trigger<OnCollisionStart>,
+    mut commands: Commands,
+    mut player_query: Query<(&Transform, &mut Inventory), (With<Player>, Without<Stunned>)>,
+    shopper_query: Query<(&LinearVelocity, &ComputedMass), With<Shopper>>,
+    mut game_rng: ResMut<GameRng>,
+    mut dropped_item_events: EventWriter<SpawnDroppedItem>,
+) {
+    let Ok((player_transform, mut inventory)) = player_query.get_mut(trigger.target()) else {
+        return;
+    };
+    let Ok((shopper_velocity, shopper_mass)) = shopper_query.get(trigger.collider) else {
+        return;
+    };
+
+    let momentum = shopper_velocity.length() * shopper_mass.value();
+    if momentum < KNOCKDOWN_MOMENTUM {
+        return;
+    }
+
+    log::info!("Player was knocked down by {}", trigger.collider);
+    commands.entity(trigger.target()).insert(Stunned {
+        timer: Timer::from_seconds(STUN_DURATION, TimerMode::Once),
+    });
+
+    // Spill a few random items onto the floor around the player
+    let mut held = inventory.0.values().sum::<u32>();
+    for _ in 0..held.min(MAX_SPILLED_ITEMS) {
+        // Every single held item is as likely to spill, so bigger stacks lose more
+        let mut roll = game_rng.u32(0..held);
+        let Some(item) = inventory
+            .sorted_items()
+            .into_iter()
+            .find_map(|(item, quantity)| {
+                if roll < quantity {
+                    Some(item)
                 } else {
-                    player_inventory.0.insert(shelf.main_item, 1);
+                    roll -= quantity;
+                    None
                 }
-                commands.trigger(PlayerPickedItem);
+            })
+        else {
+            break;
+        };
+        held -= 1;
+
+        if let Some(quantity) = inventory.0.get_mut(&item) {
+            *quantity -= 1;
+            if *quantity == 0 {
+                inventory.0.remove(&item);
             }
         }
+
+        // Fling the item away from the player
+        let direction = Vec2::from_angle(game_rng.f32() * std::f32::consts::TAU);
+        dropped_item_events.write(SpawnDroppedItem {
+            position: player_transform.translation.truncate() + direction * 60.0,
+            velocity: direction * (250.0 + game_rng.f32() * 150.0),
+            item,
+            quantity: 1,
+        });
+    }
+}
+
+fn player_stunned(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut player_query: Query<(Entity, &mut Stunned, &mut Sprite)>,
+) {
+    for (player_entity, mut stunned, mut sprite) in player_query.iter_mut() {
+        if stunned.timer.tick(time.delta()).just_finished() {
+            commands.entity(player_entity).remove::<Stunned>();
+            sprite.color = Color::WHITE;
+        } else {
+            // Grey out the player while they're down
+            sprite.color = GRAY.into();
+        }
     }
 }
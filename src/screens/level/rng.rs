@@ -0,0 +1,23 @@
+use bevy::prelude::*;
+use fastrand::Rng;
+
+/// Source of all gameplay randomness, seeded from [`RunSeed`](crate::screens::RunSeed) at the
+/// start of every round so a round can be replayed.
+#[derive(Resource, Deref, DerefMut)]
+pub struct GameRng(Rng);
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self(Rng::with_seed(seed))
+    }
+
+    /// Splits off a generator for a single entity, so systems running in parallel don't have to
+    /// share one and stay deterministic.
+    pub fn fork(&mut self) -> EntityRng {
+        EntityRng(self.0.fork())
+    }
+}
+
+/// An entity's own share of the [`GameRng`].
+#[derive(Component, Deref, DerefMut)]
+pub struct EntityRng(Rng);
@@ -0,0 +1,174 @@
+use crate::{
+    GameAssets,
+    screens::{
+        LossReason, Profile, Screen,
+        level::{GameTimer, LEVEL_PATH, LevelState},
+    },
+    storage::Storage,
+};
+use bevy::{color::palettes::css::*, prelude::*};
+
+/// How long the 3-2-1 countdown lasts before the round starts.
+const COUNTDOWN_DURATION: f32 = 3.0;
+/// How long the slow-motion phase lasts at the end of a round, in real time.
+const ENDING_DURATION: f32 = 1.5;
+/// How fast time runs during the slow-motion phase.
+const ENDING_TIME_SCALE: f32 = 0.25;
+
+/// How a round ended.
+#[derive(Clone, Copy)]
+pub enum RoundOutcome {
+    Won,
+    Lost(LossReason),
+}
+
+/// Ends the round, playing the slow-motion ending before showing the results screen.
+#[derive(Event)]
+pub struct RoundEnded(pub RoundOutcome);
+
+#[derive(Resource)]
+struct Countdown(Timer);
+
+#[derive(Resource)]
+struct Ending {
+    outcome: RoundOutcome,
+    timer: Timer,
+}
+
+#[derive(Component)]
+struct CountdownUI;
+
+pub fn plugin(app: &mut App) {
+    // Register events
+    app.add_event::<RoundEnded>();
+
+    // Countdown systems
+    app.add_systems(OnEnter(LevelState::Countdown), start_countdown);
+    app.add_systems(OnExit(LevelState::Countdown), despawn_countdown_ui);
+    app.add_systems(Update, countdown.run_if(in_state(LevelState::Countdown)));
+
+    // Ending systems
+    app.add_systems(
+        Update,
+        end_round
+            .run_if(on_event::<RoundEnded>)
+            .run_if(in_state(LevelState::Playing)),
+    );
+    app.add_systems(OnEnter(LevelState::Ending), start_slow_motion);
+    app.add_systems(OnExit(LevelState::Ending), stop_slow_motion);
+    app.add_systems(Update, ending.run_if(in_state(LevelState::Ending)));
+}
+
+fn start_countdown(mut commands: Commands, assets: Res<GameAssets>) {
+    commands.insert_resource(Countdown(Timer::from_seconds(
+        COUNTDOWN_DURATION,
+        TimerMode::Once,
+    )));
+
+    commands.spawn((
+        Name::new("Countdown UI"),
+        CountdownUI,
+        Node {
+            width: Val::Vw(100.0),
+            height: Val::Vh(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        children![(
+            Name::new("Countdown Text"),
+            Text::new(format!("{}", COUNTDOWN_DURATION.ceil())),
+            TextColor(GOLD.into()),
+            TextFont {
+                font: assets.ui_font.clone(),
+                font_size: 96.0,
+                ..Default::default()
+            },
+            TextLayout::new_with_justify(JustifyText::Center),
+        )],
+    ));
+}
+
+fn despawn_countdown_ui(mut commands: Commands, query: Single<Entity, With<CountdownUI>>) {
+    commands.entity(query.entity()).despawn();
+}
+
+fn countdown(
+    time: Res<Time>,
+    mut countdown: ResMut<Countdown>,
+    mut text_query: Query<&mut Text>,
+    ui_query: Single<&Children, With<CountdownUI>>,
+    mut next_level_state: ResMut<NextState<LevelState>>,
+) {
+    countdown.0.tick(time.delta());
+
+    for child in ui_query.iter() {
+        if let Ok(mut text) = text_query.get_mut(child) {
+            text.0 = format!("{}", countdown.0.remaining_secs().ceil());
+        }
+    }
+
+    if countdown.0.just_finished() {
+        log::info!("Round started");
+        next_level_state.set(LevelState::Playing);
+    }
+}
+
+fn end_round(
+    mut commands: Commands,
+    mut round_events: EventReader<RoundEnded>,
+    game_timer: Res<GameTimer>,
+    mut profile: ResMut<Profile>,
+    storage: Res<Storage>,
+    mut next_level_state: ResMut<NextState<LevelState>>,
+) {
+    // Only the first reason to end the round counts
+    let Some(RoundEnded(outcome)) = round_events.read().next() else {
+        return;
+    };
+    round_events.clear();
+
+    log::info!("Round over");
+    if let RoundOutcome::Won = outcome {
+        let time = game_timer.0.elapsed_secs();
+        if profile.record_win(LEVEL_PATH, time) {
+            log::info!("New best time of {:.1}s", time);
+        }
+        profile.unlocked_levels.insert(LEVEL_PATH.to_string());
+        storage.save_or_log(&*profile);
+    }
+
+    commands.insert_resource(Ending {
+        outcome: *outcome,
+        timer: Timer::from_seconds(ENDING_DURATION, TimerMode::Once),
+    });
+    next_level_state.set(LevelState::Ending);
+}
+
+fn start_slow_motion(mut virtual_time: ResMut<Time<Virtual>>) {
+    virtual_time.set_relative_speed(ENDING_TIME_SCALE);
+}
+
+fn stop_slow_motion(mut virtual_time: ResMut<Time<Virtual>>) {
+    virtual_time.set_relative_speed(1.0);
+}
+
+fn ending(
+    mut commands: Commands,
+    // Real time, so slow motion doesn't drag the ending out
+    time: Res<Time<Real>>,
+    mut ending: ResMut<Ending>,
+    mut next_screen: ResMut<NextState<Screen>>,
+) {
+    if !ending.timer.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    match ending.outcome {
+        RoundOutcome::Won => next_screen.set(Screen::Win),
+        RoundOutcome::Lost(loss_reason) => {
+            commands.insert_resource(loss_reason);
+            next_screen.set(Screen::GameOver);
+        }
+    }
+}
@@ -2,23 +2,65 @@ use crate::{
     GameAssets,
     screens::{
         Screen,
-        level::{EntityOrientation, GameLayer, Item, player::Player, shopper::Shopper},
+        level::{
+            EntityOrientation, GameLayer, Item, dropped_item::SpawnDroppedItem, employee::Employee,
+            navigation::NavObstacle, player::Player, rng::GameRng, shopper::Shopper,
+        },
     },
 };
 use avian2d::prelude::*;
 use bevy::{color::palettes::css::*, prelude::*};
+use serde::Deserialize;
 
-#[derive(Event)]
+/// Momentum (mass times speed) above which bumping into a shelf knocks items off it.
+const KNOCK_MOMENTUM: f32 = 300_000.0;
+/// Most items knocked off a shelf by a single bump.
+const MAX_KNOCKED_ITEMS: u32 = 2;
+const SHELF_SIZE: Vec2 = Vec2::new(360.0, 120.0);
+/// Size of the sensors along both long sides of a shelf, where items can be taken from.
+const SENSOR_SIZE: Vec2 = Vec2::new(0.85 * SHELF_SIZE.x, 0.4 * SHELF_SIZE.y);
+
+#[derive(Event, Clone, Deserialize)]
 pub struct SpawnShelf {
     pub position: Vec2,
     pub orientation: EntityOrientation,
     pub main_item: Item,
+    pub stock: u32,
 }
 
 #[derive(Component, Reflect)]
 #[reflect(Component)]
 pub struct Shelf {
     pub main_item: Item,
+    /// Items left on the shelf, shared between the player and shoppers.
+    pub stock: u32,
+    pub capacity: u32,
+}
+
+impl Shelf {
+    /// Takes one item off the shelf, returning `false` if it was already empty.
+    pub fn take(&mut self) -> bool {
+        if self.stock == 0 {
+            return false;
+        }
+        self.stock -= 1;
+        true
+    }
+
+    /// Middle of the shelf's sensor on the side closest to `from`, where an NPC coming from
+    /// there should head to reach it. The shelf's short ends have no sensors.
+    pub fn access_point(shelf_transform: &Transform, from: Vec2) -> Vec2 {
+        let offset = shelf_transform.rotation * Vec3::Y * (SHELF_SIZE.y + SENSOR_SIZE.y) / 2.0;
+        let center = shelf_transform.translation;
+        [center + offset, center - offset]
+            .into_iter()
+            .map(Vec3::truncate)
+            .min_by(|a, b| {
+                a.distance_squared(from)
+                    .total_cmp(&b.distance_squared(from))
+            })
+            .unwrap_or(center.truncate())
+    }
 }
 
 #[derive(Component)]
@@ -33,9 +75,12 @@ pub fn plugin(app: &mut App) {
 
     // (De)spawn systems
     app.add_systems(
-        Update,
-        spawn_shelves.run_if(in_state(Screen::Level).and(on_event::<SpawnShelf>)),
+        FixedUpdate,
+        spawn_shelves
+            .run_if(on_event::<SpawnShelf>)
+            .run_if(in_state(Screen::Level)),
     );
+    app.add_systems(Update, shelf_stock_label.run_if(in_state(Screen::Level)));
     app.add_systems(OnExit(Screen::Level), despawn_shelves);
 }
 
@@ -44,47 +89,52 @@ fn spawn_shelves(
     mut events: EventReader<SpawnShelf>,
     assets: Res<GameAssets>,
 ) {
-    let shelf_size = Vec2::new(360.0, 120.0);
-    let sensor_size = Vec2::new(0.85 * shelf_size.x, 0.4 * shelf_size.y);
-
     for event in events.read() {
         commands
             .spawn((
                 Name::new("Shelf"),
                 Shelf {
                     main_item: event.main_item,
+                    stock: event.stock,
+                    capacity: event.stock,
                 },
-                Sprite::from_color(SLATE_GRAY, shelf_size),
+                Sprite::from_color(SLATE_GRAY, SHELF_SIZE),
                 Transform {
                     translation: event.position.extend(0.0),
                     rotation: event.orientation.into(),
                     ..Default::default()
                 },
                 RigidBody::Static,
-                Collider::rectangle(shelf_size.x, shelf_size.y),
-                CollisionLayers::new(GameLayer::Environment, [GameLayer::Player, GameLayer::NPC]),
-                Text2d::new(event.main_item.to_string()),
+                Collider::rectangle(SHELF_SIZE.x, SHELF_SIZE.y),
+                CollisionLayers::new(
+                    GameLayer::Environment,
+                    [GameLayer::Player, GameLayer::NPC, GameLayer::Pickup],
+                ),
+                CollisionEventsEnabled,
+                NavObstacle { size: SHELF_SIZE },
+                Text2d::default(),
                 TextFont {
                     font: assets.game_font.clone(),
                     font_size: 32.0,
                     ..Default::default()
                 },
             ))
+            .observe(shelf_knocked)
             .with_children(|parent| {
                 // Shopper Sensors
                 parent
                     .spawn((
                         Name::new("Shopper Sensor 1"),
                         ShopperSensor,
-                        Transform::from_xyz(0.0, (shelf_size.y + sensor_size.y) / 2.0, 0.0),
+                        Transform::from_xyz(0.0, (SHELF_SIZE.y + SENSOR_SIZE.y) / 2.0, 0.0),
                         Sensor,
-                        Collider::rectangle(sensor_size.x, sensor_size.y),
+                        Collider::rectangle(SENSOR_SIZE.x, SENSOR_SIZE.y),
                         CollisionLayers::new(
                             GameLayer::Environment,
                             [GameLayer::Player, GameLayer::NPC],
                         ),
                         CollisionEventsEnabled,
-                        // Sprite::from_color(LIGHT_BLUE.with_alpha(0.5), sensor_size),
+                        // Sprite::from_color(LIGHT_BLUE.with_alpha(0.5), SENSOR_SIZE),
                     ))
                     .observe(shopper_approached_shelf)
                     .observe(shopper_departed_shelf);
@@ -92,26 +142,18 @@ fn spawn_shelves(
                     .spawn((
                         Name::new("Shopper Sensor 2"),
                         ShopperSensor,
-                        Transform::from_xyz(0.0, -(shelf_size.y + sensor_size.y) / 2.0, 0.0),
+                        Transform::from_xyz(0.0, -(SHELF_SIZE.y + SENSOR_SIZE.y) / 2.0, 0.0),
                         Sensor,
-                        Collider::rectangle(sensor_size.x, sensor_size.y),
+                        Collider::rectangle(SENSOR_SIZE.x, SENSOR_SIZE.y),
                         CollisionLayers::new(
                             GameLayer::Environment,
                             [GameLayer::Player, GameLayer::NPC],
                         ),
                         CollisionEventsEnabled,
-                        // Sprite::from_color(LIGHT_BLUE.with_alpha(0.5), sensor_size),
+                        // Sprite::from_color(LIGHT_BLUE.with_alpha(0.5), SENSOR_SIZE),
                     ))
                     .observe(shopper_approached_shelf)
                     .observe(shopper_departed_shelf);
-
-                // Collider to prevent NPC shoppers getting stuck on shelf sides
-                // parent.spawn((
-                //     Transform::from_rotation(Quat::from_rotation_z(FRAC_PI_2)),
-                //     Collider::capsule(shelf_size.y / 2.0, shelf_size.x),
-                //     CollisionLayers::new(GameLayer::Shelf, [GameLayer::NPC]),
-                //     Friction::new(0.0),
-                // ));
             });
     }
 }
@@ -122,11 +164,67 @@ fn despawn_shelves(mut commands: Commands, shelf_query: Query<Entity, With<Shelf
     }
 }
 
+fn shelf_stock_label(
+    mut shelf_query: Query<(&Shelf, &mut Text2d, &mut TextColor), Changed<Shelf>>,
+) {
+    for (shelf, mut text, mut text_color) in shelf_query.iter_mut() {
+        text.0 = format!("{} ({}/{})", shelf.main_item, shelf.stock, shelf.capacity);
+        *text_color = if shelf.stock == 0 {
+            RED.into()
+        } else {
+            WHITE.into()
+        };
+    }
+}
+
+fn shelf_knocked(
+    trigger: Trigger<OnCollisionStart>,
+    mut shelf_query: Query<(&Transform, &mut Shelf)>,
+    bumper_query: Query<
+        (&Transform, &LinearVelocity, &ComputedMass),
+        (Or<(With<Player>, With<Shopper>)>, Without<Shelf>),
+    >,
+    mut game_rng: ResMut<GameRng>,
+    mut dropped_item_events: EventWriter<SpawnDroppedItem>,
+) {
+    let Ok((shelf_transform, mut shelf)) = shelf_query.get_mut(trigger.target()) else {
+        return;
+    };
+    let Ok((bumper_transform, bumper_velocity, bumper_mass)) = bumper_query.get(trigger.collider)
+    else {
+        return;
+    };
+
+    let momentum = bumper_velocity.length() * bumper_mass.value();
+    if momentum < KNOCK_MOMENTUM || shelf.stock == 0 {
+        return;
+    }
+
+    // Items fall off on the side that was hit
+    let direction = (bumper_transform.translation - shelf_transform.translation)
+        .truncate()
+        .normalize_or_zero();
+    let knocked = (1 + game_rng.u32(..MAX_KNOCKED_ITEMS)).min(shelf.stock);
+    shelf.stock -= knocked;
+
+    log::debug!("{} knocked {} items off shelf", trigger.collider, knocked);
+    for _ in 0..knocked {
+        let spread = Vec2::from_angle(game_rng.f32() - 0.5).rotate(direction);
+        dropped_item_events.write(SpawnDroppedItem {
+            position: shelf_transform.translation.truncate() + direction * 100.0,
+            velocity: spread * (150.0 + game_rng.f32() * 100.0),
+            item: shelf.main_item,
+            quantity: 1,
+        });
+    }
+}
+
 fn shopper_approached_shelf(
     trigger: Trigger<OnCollisionStart>,
     child_of_query: Query<&ChildOf>,
     mut player_query: Query<&mut Player>,
     mut shopper_query: Query<&mut Shopper>,
+    mut employee_query: Query<&mut Employee>,
 ) {
     let sensor_entity = trigger.target();
 
@@ -143,6 +241,13 @@ fn shopper_approached_shelf(
                 sensor_entity
             );
             shopper.current_shelf = Some(shelf_entity);
+        } else if let Ok(mut employee) = employee_query.get_mut(trigger.collider) {
+            log::debug!(
+                "Employee {} has approached shelf sensor {}",
+                trigger.collider,
+                sensor_entity
+            );
+            employee.current_shelf = Some(shelf_entity);
         }
     }
 }
@@ -151,6 +256,7 @@ fn shopper_departed_shelf(
     trigger: Trigger<OnCollisionEnd>,
     mut player_query: Query<&mut Player>,
     mut shopper_query: Query<&mut Shopper>,
+    mut employee_query: Query<&mut Employee>,
 ) {
     if let Ok(mut player) = player_query.get_mut(trigger.collider) {
         log::debug!("Player has departed shelf sensor {}", trigger.target());
@@ -162,5 +268,12 @@ fn shopper_departed_shelf(
             trigger.target()
         );
         shopper.current_shelf = None;
+    } else if let Ok(mut employee) = employee_query.get_mut(trigger.collider) {
+        log::debug!(
+            "Employee {} has departed shelf sensor {}",
+            trigger.collider,
+            trigger.target()
+        );
+        employee.current_shelf = None;
     }
 }
@@ -1,48 +1,343 @@
+use std::collections::HashMap;
+
 use crate::{
     GameAssets,
     screens::{
         Screen,
         level::{
-            GameLayer, Inventory,
+            GameLayer, Inventory, Item, LevelState,
+            behavior::{ActionStatus, Behavior, BehaviorTree},
+            checkout::{CheckoutCounter, queue_slot_position},
+            dropped_item::DroppedItem,
+            exit::{EXIT_RADIUS, Exit},
+            navigation::{NavGrid, NavPath, follow_path},
             player::{Player, PlayerPickedItem},
+            rng::{EntityRng, GameRng},
             shelf::Shelf,
+            steering::Steering,
         },
     },
 };
 use avian2d::prelude::*;
 use bevy::{color::palettes::css::*, prelude::*};
+use fastrand::Rng;
+use serde::Deserialize;
 
-const PANIC_THRESHOLD: u32 = 5;
+/// Fraction of a shopper's panic threshold at which they become uneasy.
+const UNEASY_FRACTION: f32 = 0.4;
+/// Fraction of a shopper's panic threshold at which they become alarmed.
+const ALARMED_FRACTION: f32 = 0.7;
+/// How far shoppers can see.
 const PANIC_DISTANCE: f32 = 300.0;
+/// Half the width of a shopper's vision cone, in radians.
+const VISION_HALF_ANGLE: f32 = std::f32::consts::FRAC_PI_3;
+/// Panic lost per second while the player is further than [`PANIC_DISTANCE`] away.
+const PANIC_DECAY_RATE: f32 = 0.4;
+/// Distance within which wandering shoppers notice items lying on the floor.
+const NOTICE_DISTANCE: f32 = 250.0;
+/// How close a shopper needs to be to their queue spot to count as standing in it.
+const QUEUE_SLOT_TOLERANCE: f32 = 20.0;
+/// Seconds a shopper browses around before checking on their list again.
+const WANDER_DURATION: f32 = 2.0;
+/// Seconds a shopper spends at a shelf at most before giving up on it.
+const TAKING_DURATION: f32 = 8.0;
 
-#[derive(Event)]
+#[derive(Event, Clone, Deserialize)]
 pub struct SpawnShopper {
     pub position: Vec2,
+    /// Name of the [`ShopperArchetype`] to spawn, or a weighted random one if not given.
+    #[serde(default)]
+    pub archetype: Option<String>,
+}
+
+/// A kind of shopper, with their own stats and habits.
+#[derive(Clone, Reflect, Deserialize)]
+#[serde(default)]
+pub struct ShopperArchetype {
+    pub name: String,
+    /// Relative chance of a randomly chosen shopper being of this kind.
+    pub weight: f32,
+    /// Top speed while calm.
+    pub speed: f32,
+    /// Seconds it takes to take one item off a shelf.
+    pub taking_interval: f32,
+    /// Most different items on their shopping list.
+    pub max_list_items: usize,
+    /// Most of a single item on their shopping list.
+    pub max_list_quantity: u32,
+    /// Panic at which the shopper starts stampeding, lower tiers are reached proportionally
+    /// sooner.
+    pub panic_threshold: f32,
+    /// Collider density, heavier shoppers are harder to push around and hit harder.
+    pub density: f32,
+}
+
+impl Default for ShopperArchetype {
+    fn default() -> Self {
+        Self {
+            name: "Regular".to_string(),
+            weight: 1.0,
+            speed: 100.0,
+            taking_interval: 1.5,
+            max_list_items: 3,
+            max_list_quantity: 4,
+            panic_threshold: 5.0,
+            density: 1.0,
+        }
+    }
+}
+
+/// Every kind of shopper that can turn up in the current level.
+#[derive(Resource, Reflect, Default, Clone)]
+#[reflect(Resource)]
+pub struct ShopperArchetypes(pub Vec<ShopperArchetype>);
+
+impl ShopperArchetypes {
+    /// The archetype called `name`, or a weighted random one if no name is given.
+    fn choose(&self, name: Option<&str>, rng: &mut Rng) -> ShopperArchetype {
+        if let Some(name) = name {
+            if let Some(archetype) = self.0.iter().find(|archetype| archetype.name == name) {
+                return archetype.clone();
+            }
+            log::warn!("Unknown shopper archetype {}, using the default", name);
+            return ShopperArchetype::default();
+        }
+
+        let total_weight = self.0.iter().map(|archetype| archetype.weight).sum::<f32>();
+        let mut roll = rng.f32() * total_weight;
+        for archetype in self.0.iter() {
+            if roll < archetype.weight {
+                return archetype.clone();
+            }
+            roll -= archetype.weight;
+        }
+        self.0.last().cloned().unwrap_or_default()
+    }
 }
 
 #[derive(Component, Reflect)]
 #[reflect(Component)]
 pub struct Shopper {
     pub current_shelf: Option<Entity>,
-    panic_meter: u32,
+    /// Checkout counter the shopper is lining up or paying at.
+    pub queued_counter: Option<Entity>,
+    panic_meter: f32,
+    /// Tier the shopper was at when it was last checked, to tell when it changes.
+    previous_tier: PanicTier,
+    /// Paces how often this shopper spreads panic while panicked.
+    contagion_timer: Timer,
+    archetype: ShopperArchetype,
 }
 
-#[derive(Component, Reflect)]
+impl Shopper {
+    pub fn panic_tier(&self) -> PanicTier {
+        PanicTier::from_meter(self.panic_meter, self.archetype.panic_threshold)
+    }
+
+    fn is_stampeding(&self) -> bool {
+        self.panic_meter >= self.archetype.panic_threshold
+    }
+}
+
+/// How worked up a shopper is, derived from their panic meter.
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PanicTier {
+    Calm,
+    /// Moves faster but otherwise keeps shopping.
+    Uneasy,
+    /// Drops everything and runs away from the player.
+    Alarmed,
+    /// Charges at the player.
+    Stampeding,
+}
+
+impl PanicTier {
+    fn from_meter(panic_meter: f32, panic_threshold: f32) -> Self {
+        if panic_meter >= panic_threshold {
+            Self::Stampeding
+        } else if panic_meter >= ALARMED_FRACTION * panic_threshold {
+            Self::Alarmed
+        } else if panic_meter >= UNEASY_FRACTION * panic_threshold {
+            Self::Uneasy
+        } else {
+            Self::Calm
+        }
+    }
+
+    /// Whether the shopper has stopped shopping to run away or charge.
+    pub fn is_panicking(self) -> bool {
+        matches!(self, Self::Alarmed | Self::Stampeding)
+    }
+
+    fn speed_multiplier(self) -> f32 {
+        match self {
+            Self::Calm => 1.0,
+            Self::Uneasy => 1.3,
+            Self::Alarmed => 1.6,
+            Self::Stampeding => 2.0,
+        }
+    }
+
+    fn indicator_color(self) -> Color {
+        match self {
+            Self::Calm => WHITE.into(),
+            Self::Uneasy => YELLOW.into(),
+            Self::Alarmed => ORANGE.into(),
+            Self::Stampeding => RED.into(),
+        }
+    }
+}
+
+/// How panic spreads from panicked shoppers to calm ones nearby.
+#[derive(Resource, Reflect, Clone, Deserialize)]
+#[reflect(Resource)]
+pub struct PanicContagion {
+    /// Distance within which a panicked shopper unsettles others.
+    pub radius: f32,
+    /// Seconds between each spread of panic from a panicked shopper.
+    pub interval: f32,
+}
+
+impl Default for PanicContagion {
+    fn default() -> Self {
+        Self {
+            radius: 200.0,
+            interval: 1.5,
+        }
+    }
+}
+
+/// Triggered every time a panicked shopper raises the panic meter of another shopper.
+#[derive(Event)]
+pub struct PanicSpread {
+    pub source: Entity,
+    pub target: Entity,
+}
+
+/// Triggered on a shopper whenever its panic meter crosses into another [`PanicTier`].
+#[derive(Event)]
+pub struct PanicTierChanged {
+    pub from: PanicTier,
+    pub to: PanicTier,
+}
+
+/// Items a shopper came in to buy, written once they've entered the store.
+#[derive(Component, Default, Reflect)]
 #[reflect(Component)]
-enum ShopperState {
-    Wandering {
-        timer: Timer,
-        direction: Vec2,
-    },
-    Traveling {
-        target_shelf: Entity,
-    },
-    Taking {
-        timer: Timer,
-        taking_timer: Timer,
-        target_shelf: Entity,
-    },
-    Panicked,
+struct ShoppingList(HashMap<Item, u32>);
+
+impl ShoppingList {
+    /// How many more of `item` the shopper still has to find.
+    fn remaining(&self, item: Item, inventory: &Inventory) -> u32 {
+        let wanted = self.0.get(&item).copied().unwrap_or(0);
+        let carried = inventory.0.get(&item).copied().unwrap_or(0);
+        wanted.saturating_sub(carried)
+    }
+
+    /// Whether the shopper has found everything on their list that the store carries.
+    fn is_done(&self, inventory: &Inventory, carried_items: &[Item]) -> bool {
+        self.0
+            .keys()
+            .all(|item| !carried_items.contains(item) || self.remaining(*item, inventory) == 0)
+    }
+}
+
+/// Every item the store has a shelf for, whether it's in stock or not.
+fn carried_items<'a>(shelves: impl Iterator<Item = &'a Shelf>) -> Vec<Item> {
+    let mut items = Vec::new();
+    for shelf in shelves {
+        if !items.contains(&shelf.main_item) {
+            items.push(shelf.main_item);
+        }
+    }
+    items
+}
+
+// Shopper actions, run by the shopper's behavior tree
+
+/// Browsing around in a random direction, until something catches the shopper's eye.
+#[derive(Component, Reflect, Clone, Default)]
+#[reflect(Component)]
+struct Wandering {
+    direction: Option<Vec2>,
+}
+
+/// Heading to the closest shelf stocking something on the shopping list.
+#[derive(Component, Reflect, Clone, Default)]
+#[reflect(Component)]
+struct Traveling {
+    target_shelf: Option<Entity>,
+}
+
+/// Taking items on the list off the shelf the shopper is standing at.
+#[derive(Component, Reflect, Clone, Default)]
+#[reflect(Component)]
+struct Taking {
+    taking_timer: Option<Timer>,
+}
+
+/// Lining up at the checkout counter with the shortest line.
+#[derive(Component, Reflect, Clone, Default)]
+#[reflect(Component)]
+struct Queuing;
+
+/// Paying at the front of the line.
+#[derive(Component, Reflect, Clone, Default)]
+#[reflect(Component)]
+struct Paying {
+    timer: Option<Timer>,
+}
+
+/// Heading home through the closest exit once there's nothing left to buy.
+#[derive(Component, Reflect, Clone, Default)]
+#[reflect(Component)]
+struct Leaving {
+    exit: Option<Entity>,
+}
+
+/// Going for a free item lying on the floor nearby.
+#[derive(Component, Reflect, Clone, Default)]
+#[reflect(Component)]
+struct Grabbing {
+    target_item: Option<Entity>,
+}
+
+/// Running away from the player while alarmed.
+#[derive(Component, Reflect, Clone, Default)]
+#[reflect(Component)]
+pub struct Fleeing;
+
+/// Charging at the player while stampeding.
+#[derive(Component, Reflect, Clone, Default)]
+#[reflect(Component)]
+pub struct Panicked;
+
+/// What shoppers do, most urgent first: panic, find what's on their list, pay for it, head home,
+/// and otherwise browse around.
+fn shopper_behavior() -> Behavior {
+    Behavior::repeat(Behavior::Selector(vec![
+        Behavior::guard(
+            "Stampeding",
+            |shopper: &Shopper| shopper.panic_tier() == PanicTier::Stampeding,
+            Behavior::action(Panicked),
+        ),
+        Behavior::guard(
+            "Alarmed",
+            |shopper: &Shopper| shopper.panic_tier() == PanicTier::Alarmed,
+            Behavior::action(Fleeing),
+        ),
+        Behavior::Sequence(vec![
+            Behavior::action(Traveling::default()),
+            Behavior::timeout(TAKING_DURATION, Behavior::action(Taking::default())),
+        ]),
+        Behavior::Sequence(vec![
+            Behavior::action(Queuing),
+            Behavior::action(Paying::default()),
+        ]),
+        Behavior::action(Leaving::default()),
+        Behavior::action(Grabbing::default()),
+        Behavior::timeout(WANDER_DURATION, Behavior::action(Wandering::default())),
+    ]))
 }
 
 #[derive(Component)]
@@ -51,51 +346,104 @@ struct PanicMeterIndicator;
 pub fn plugin(app: &mut App) {
     // Register necessary types
     app.register_type::<Shopper>();
-    app.register_type::<ShopperState>();
+    app.register_type::<ShoppingList>();
+    app.register_type::<Wandering>();
+    app.register_type::<Traveling>();
+    app.register_type::<Taking>();
+    app.register_type::<Queuing>();
+    app.register_type::<Paying>();
+    app.register_type::<Leaving>();
+    app.register_type::<Grabbing>();
+    app.register_type::<Fleeing>();
+    app.register_type::<Panicked>();
+    app.register_type::<PanicContagion>();
+    app.register_type::<ShopperArchetypes>();
+
+    // Add resources
+    app.init_resource::<PanicContagion>();
+    app.init_resource::<ShopperArchetypes>();
 
     // Register spawn events
     app.add_event::<SpawnShopper>();
 
     // Shopper systems
     app.add_systems(
-        Update,
+        FixedUpdate,
         (
             spawn_shoppers.run_if(on_event::<SpawnShopper>),
-            panic_meter_indicator_text,
-            panic_meter_indicator_rotation,
-            shopper_state_machine,
-            shopper_wandering.after(shopper_state_machine),
-            shopper_traveling.after(shopper_state_machine),
-            shopper_taking.after(shopper_state_machine),
-            shopper_panicked.after(shopper_state_machine),
+            (
+                panic_contagion,
+                panic_decay,
+                panic_tier_movement,
+                panic_interrupts.after(panic_contagion).after(panic_decay),
+                panic_tier_changes.after(panic_contagion).after(panic_decay),
+                write_shopping_lists,
+                shopper_leaving_queue,
+                shopper_wandering,
+                shopper_traveling,
+                shopper_taking,
+                (shopper_choosing_counter, shopper_queuing).chain(),
+                shopper_paying,
+                shopper_leaving,
+                shopper_grabbing,
+                shopper_fleeing,
+                shopper_panicked,
+            )
+                .run_if(in_state(LevelState::Playing)),
         )
             .run_if(in_state(Screen::Level)),
     );
+    app.add_systems(
+        Update,
+        (panic_meter_indicator_text, panic_meter_indicator_rotation)
+            .run_if(in_state(Screen::Level)),
+    );
     app.add_systems(OnExit(Screen::Level), despawn_shoppers);
 
     // Add observers
     app.add_observer(panic_meter);
+
+    // Add debug systems
+    #[cfg(feature = "dev")]
+    app.add_systems(Update, draw_vision_cones.run_if(in_state(Screen::Level)));
 }
 
 fn spawn_shoppers(
     mut commands: Commands,
     assets: Res<GameAssets>,
+    contagion: Res<PanicContagion>,
+    archetypes: Res<ShopperArchetypes>,
+    mut game_rng: ResMut<GameRng>,
     mut events: EventReader<SpawnShopper>,
 ) {
     let shopper_size = Vec2::new(72.0, 36.0);
 
     for event in events.read() {
+        let archetype = archetypes.choose(event.archetype.as_deref(), &mut game_rng);
+        let speed = archetype.speed;
+        let density = archetype.density;
+
         commands.spawn((
-            Name::new("Shopper"),
+            Name::new(format!("{} Shopper", archetype.name)),
             Shopper {
                 current_shelf: None,
-                panic_meter: 0,
+                queued_counter: None,
+                panic_meter: 0.0,
+                previous_tier: PanicTier::Calm,
+                contagion_timer: Timer::from_seconds(contagion.interval, TimerMode::Repeating),
+                archetype,
             },
-            ShopperState::Wandering {
-                timer: Timer::from_seconds(2.0, TimerMode::Once),
-                direction: Vec2::ZERO,
-            },
-            Inventory::default(),
+            // Shopping behaviour
+            (
+                BehaviorTree::new(shopper_behavior()),
+                game_rng.fork(),
+                Inventory::default(),
+                NavPath::default(),
+                Steering {
+                    avoid_player: true,
+                    ..Default::default()
+                },
+            ),
             // Sprite::from_color(YELLOW, shopper_size),
             Sprite {
                 image: assets.shopper_npc.clone(),
@@ -106,22 +454,27 @@ fn spawn_shoppers(
                 translation: event.position.extend(0.0),
                 ..default()
             },
-            RigidBody::Dynamic,
-            Collider::rectangle(shopper_size.x, shopper_size.y),
-            CollisionLayers::new(
-                GameLayer::NPC,
-                [
-                    GameLayer::Default,
-                    GameLayer::Player,
+            // Physics
+            (
+                RigidBody::Dynamic,
+                Collider::rectangle(shopper_size.x, shopper_size.y),
+                ColliderDensity(density),
+                CollisionLayers::new(
                     GameLayer::NPC,
-                    GameLayer::Environment,
-                ],
+                    [
+                        GameLayer::Default,
+                        GameLayer::Player,
+                        GameLayer::NPC,
+                        GameLayer::Environment,
+                        GameLayer::Pickup,
+                    ],
+                ),
+                CollisionEventsEnabled,
+                LinearDamping(1.2),
+                AngularDamping(2.0),
+                MaxLinearSpeed(speed),
+                ExternalImpulse::default().with_persistence(false),
             ),
-            CollisionEventsEnabled,
-            LinearDamping(1.2),
-            AngularDamping(2.0),
-            MaxLinearSpeed(100.0),
-            ExternalImpulse::default().with_persistence(false),
             children![(
                 Name::new("Panic Meter"),
                 PanicMeterIndicator,
@@ -147,28 +500,220 @@ fn despawn_shoppers(mut commands: Commands, query: Query<Entity, With<Shopper>>)
     }
 }
 
+/// Gives shoppers that just came in a list of things the store stocks.
+fn write_shopping_lists(
+    mut commands: Commands,
+    mut shopper_query: Query<(Entity, &Shopper, &mut EntityRng), Without<ShoppingList>>,
+    shelf_query: Query<&Shelf>,
+) {
+    let stocked_items = carried_items(shelf_query.iter());
+
+    // Wait for the shelves to be put up
+    if stocked_items.is_empty() {
+        return;
+    }
+
+    for (shopper_entity, shopper, mut rng) in shopper_query.iter_mut() {
+        let max_items = shopper.archetype.max_list_items.min(stocked_items.len());
+        let max_quantity = shopper.archetype.max_list_quantity;
+        let mut shopping_list = ShoppingList::default();
+
+        if max_items > 0 && max_quantity > 0 {
+            let mut items = stocked_items.clone();
+            rng.shuffle(&mut items);
+            let item_count = rng.usize(1..=max_items);
+            shopping_list.0 = items
+                .into_iter()
+                .take(item_count)
+                .map(|item| (item, rng.u32(1..=max_quantity)))
+                .collect();
+        }
+
+        commands.entity(shopper_entity).insert(shopping_list);
+    }
+}
+
+/// Whether a shopper can see `target`: it has to be inside their vision cone and not hidden
+/// behind a shelf or counter.
+fn can_see(
+    spatial_query: &SpatialQuery,
+    sensor_query: &Query<(), With<Sensor>>,
+    shopper_transform: &Transform,
+    target: Vec2,
+) -> bool {
+    let position = shopper_transform.translation.truncate();
+    let Ok((direction, distance)) = Dir2::new_and_length(target - position) else {
+        return true;
+    };
+    if distance > PANIC_DISTANCE {
+        return false;
+    }
+
+    let facing = (shopper_transform.rotation * Vec3::X).truncate();
+    if facing.angle_to(*direction).abs() > VISION_HALF_ANGLE {
+        return false;
+    }
+
+    // Shelf sensors are on the environment layer too, but don't block the view
+    spatial_query
+        .cast_ray_predicate(
+            position,
+            direction,
+            distance,
+            true,
+            &SpatialQueryFilter::from_mask(GameLayer::Environment),
+            &|entity| !sensor_query.contains(entity),
+        )
+        .is_none()
+}
+
 fn panic_meter(
     _trigger: Trigger<PlayerPickedItem>,
+    spatial_query: SpatialQuery,
+    mut shopper_query: Query<(&Transform, &mut Shopper)>,
+    sensor_query: Query<(), With<Sensor>>,
+    player_query: Single<&Transform, With<Player>>,
+) {
+    let player_position = player_query.translation.truncate();
+
+    // Only shoppers who actually saw the player grab the item panic
+    shopper_query
+        .par_iter_mut()
+        .for_each(|(shopper_transform, mut shopper)| {
+            if can_see(
+                &spatial_query,
+                &sensor_query,
+                shopper_transform,
+                player_position,
+            ) {
+                shopper.panic_meter += 1.0;
+            }
+        });
+}
+
+fn panic_decay(
+    time: Res<Time>,
     mut shopper_query: Query<(&Transform, &mut Shopper)>,
     player_query: Single<&Transform, With<Player>>,
 ) {
     shopper_query
         .par_iter_mut()
         .for_each(|(shopper_transform, mut shopper)| {
+            if shopper.panic_meter <= 0.0 {
+                return;
+            }
+
             let distance = shopper_transform
                 .translation
                 .truncate()
                 .distance(player_query.translation.truncate());
 
-            if distance <= PANIC_DISTANCE {
-                shopper.panic_meter += 1;
+            // Shoppers calm down once the player is out of sight
+            if distance > PANIC_DISTANCE {
+                shopper.panic_meter =
+                    (shopper.panic_meter - PANIC_DECAY_RATE * time.delta_secs()).max(0.0);
+            }
+        });
+}
+
+fn panic_tier_movement(
+    mut shopper_query: Query<(&Shopper, &mut MaxLinearSpeed, &mut Steering), Changed<Shopper>>,
+) {
+    shopper_query
+        .par_iter_mut()
+        .for_each(|(shopper, mut max_speed, mut steering)| {
+            let tier = shopper.panic_tier();
+
+            let speed = shopper.archetype.speed * tier.speed_multiplier();
+            if max_speed.0 != speed {
+                max_speed.0 = speed;
+            }
+
+            // Only calm shoppers politely make room for the player
+            let avoid_player = tier == PanicTier::Calm;
+            if steering.avoid_player != avoid_player {
+                steering.avoid_player = avoid_player;
+            }
+        });
+}
+
+fn panic_tier_changes(mut commands: Commands, mut shopper_query: Query<(Entity, &mut Shopper)>) {
+    for (entity, mut shopper) in shopper_query.iter_mut() {
+        let tier = shopper.panic_tier();
+        if tier == shopper.previous_tier {
+            continue;
+        }
+
+        let from = std::mem::replace(&mut shopper.previous_tier, tier);
+        commands.trigger_targets(PanicTierChanged { from, to: tier }, entity);
+    }
+}
+
+/// Drops whatever the shopper is doing once they're worked up enough to flee or stampede.
+fn panic_interrupts(
+    mut shopper_query: Query<(&Shopper, &mut BehaviorTree, Has<Fleeing>, Has<Panicked>)>,
+) {
+    shopper_query
+        .par_iter_mut()
+        .for_each(|(shopper, mut behavior_tree, fleeing, panicked)| {
+            let interrupt = match shopper.panic_tier() {
+                PanicTier::Stampeding => !panicked,
+                PanicTier::Alarmed => !fleeing,
+                PanicTier::Calm | PanicTier::Uneasy => false,
+            };
+            if interrupt {
+                behavior_tree.interrupt();
             }
         });
 }
 
+fn panic_contagion(
+    mut commands: Commands,
+    time: Res<Time>,
+    contagion: Res<PanicContagion>,
+    mut shopper_query: Query<(Entity, &Transform, &mut Shopper)>,
+) {
+    // Stampeding shoppers that are ready to spread their panic
+    let sources = shopper_query
+        .iter_mut()
+        .filter(|(_, _, shopper)| shopper.is_stampeding())
+        .filter_map(|(entity, transform, mut shopper)| {
+            shopper
+                .contagion_timer
+                .tick(time.delta())
+                .just_finished()
+                .then_some((entity, transform.translation.truncate()))
+        })
+        .collect::<Vec<_>>();
+
+    if sources.is_empty() {
+        return;
+    }
+
+    for (target, target_transform, mut shopper) in shopper_query.iter_mut() {
+        if shopper.is_stampeding() {
+            continue;
+        }
+
+        let target_position = target_transform.translation.truncate();
+        for &(source, source_position) in sources.iter() {
+            if source == target || source_position.distance(target_position) > contagion.radius {
+                continue;
+            }
+
+            shopper.panic_meter += 1.0;
+            commands.trigger(PanicSpread { source, target });
+
+            if shopper.is_stampeding() {
+                break;
+            }
+        }
+    }
+}
+
 fn panic_meter_indicator_text(
     shopper_query: Query<(&Shopper, &Children), Changed<Shopper>>,
-    mut panic_indicator_query: Query<&mut Text2d, With<PanicMeterIndicator>>,
+    mut panic_indicator_query: Query<(&mut Text2d, &mut TextColor), With<PanicMeterIndicator>>,
 ) {
     for (shopper, shopper_children) in shopper_query.iter() {
         let indicator = shopper_children
@@ -176,8 +721,12 @@ fn panic_meter_indicator_text(
             .filter(|e| panic_indicator_query.contains(*e))
             .next()
             .map(|e| panic_indicator_query.get_mut(e).unwrap());
-        if let Some(mut indicator) = indicator {
-            indicator.0 = "!".repeat(shopper.panic_meter as usize);
+        if let Some((mut indicator, mut indicator_color)) = indicator {
+            let text = "!".repeat(shopper.panic_meter.ceil() as usize);
+            if indicator.0 != text {
+                indicator.0 = text;
+            }
+            indicator_color.0 = shopper.panic_tier().indicator_color();
         }
     }
 }
@@ -198,175 +747,646 @@ fn panic_meter_indicator_rotation(
     }
 }
 
+/// Gives up the shopper's spot in line once they stop queuing or paying, e.g. to run away.
+fn shopper_leaving_queue(
+    mut shopper_query: Query<&mut Shopper, (Without<Queuing>, Without<Paying>)>,
+) {
+    shopper_query.par_iter_mut().for_each(|mut shopper| {
+        if shopper.queued_counter.is_some() {
+            shopper.queued_counter = None;
+        }
+    });
+}
+
+/// Closest free item lying on the floor within [`NOTICE_DISTANCE`].
+fn nearby_dropped_item(
+    shopper_transform: &Transform,
+    dropped_item_query: &Query<(Entity, &Transform), (With<DroppedItem>, Without<Shopper>)>,
+) -> Option<Entity> {
+    dropped_item_query
+        .iter()
+        .map(|(item_entity, item_transform)| {
+            let distance = shopper_transform
+                .translation
+                .truncate()
+                .distance(item_transform.translation.truncate());
+            (item_entity, distance)
+        })
+        .filter(|(_, distance)| *distance <= NOTICE_DISTANCE)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(item_entity, _)| item_entity)
+}
+
 fn shopper_wandering(
-    mut query: Query<(&mut Transform, &mut ExternalImpulse, &ShopperState), With<Shopper>>,
+    mut shopper_query: Query<
+        (
+            &mut Wandering,
+            &mut ActionStatus,
+            &mut EntityRng,
+            &mut Transform,
+            &mut ExternalImpulse,
+            &Steering,
+        ),
+        With<Shopper>,
+    >,
+    dropped_item_query: Query<(Entity, &Transform), (With<DroppedItem>, Without<Shopper>)>,
 ) {
     let wander_impulse = 400.0;
 
-    query
-        .par_iter_mut()
-        .for_each(|(mut transform, mut impulse, shopper_state)| {
-            if let ShopperState::Wandering {
-                timer: _,
-                direction,
-            } = *shopper_state
-            {
+    shopper_query.par_iter_mut().for_each(
+        |(mut wandering, mut status, mut rng, mut transform, mut impulse, steering)| {
+            if !status.is_running() {
+                return;
+            }
+
+            // Go for any free items lying around nearby
+            if nearby_dropped_item(&transform, &dropped_item_query).is_some() {
+                *status = ActionStatus::Success;
+                return;
+            }
+
+            let direction = *wandering
+                .direction
+                .get_or_insert_with(|| random_wander_direction(&mut rng));
+            let direction = steering.steer(direction);
+            if direction != Vec2::ZERO {
                 transform.rotation = Quat::from_rotation_z(direction.to_angle());
-                impulse.apply_impulse(direction * wander_impulse);
             }
-        });
+            impulse.apply_impulse(direction * wander_impulse);
+        },
+    );
 }
 
 fn shopper_traveling(
-    mut shopper_query: Query<(&mut Transform, &mut ExternalImpulse, &ShopperState), With<Shopper>>,
-    shelf_query: Query<&Transform, (With<Shelf>, Without<Shopper>)>,
+    nav_grid: Res<NavGrid>,
+    mut shopper_query: Query<
+        (
+            &mut Traveling,
+            &mut ActionStatus,
+            &Shopper,
+            Option<&ShoppingList>,
+            &Inventory,
+            &mut Transform,
+            &mut ExternalImpulse,
+            &mut NavPath,
+            &Steering,
+        ),
+        With<Shopper>,
+    >,
+    shelf_query: Query<(Entity, &Transform, &Shelf), Without<Shopper>>,
 ) {
-    let travel_impulse = 1000.0;
-
     shopper_query.par_iter_mut().for_each(
-        |(mut shopper_transform, mut shopper_impulse, shopper_state)| {
-            if let ShopperState::Traveling { target_shelf } = *shopper_state {
-                if let Ok(shelf_transform) = shelf_query.get(target_shelf) {
-                    // Calculate direction to the target shelf
-                    let direction = (shelf_transform.translation - shopper_transform.translation)
-                        .truncate()
-                        .normalize_or_zero();
-
-                    shopper_transform.rotation = Quat::from_rotation_z(direction.to_angle());
-                    shopper_impulse.apply_impulse(direction * travel_impulse);
+        |(
+            mut traveling,
+            mut status,
+            shopper,
+            shopping_list,
+            inventory,
+            mut shopper_transform,
+            mut shopper_impulse,
+            mut nav_path,
+            steering,
+        )| {
+            if !status.is_running() {
+                return;
+            }
+
+            let target_shelf = match traveling.target_shelf {
+                Some(target_shelf) => target_shelf,
+                None => {
+                    let distance_to = |transform: &Transform| {
+                        shopper_transform
+                            .translation
+                            .distance_squared(transform.translation)
+                    };
+
+                    // Closest shelf that has something still on the list in stock
+                    let closest_shelf = shopping_list.and_then(|shopping_list| {
+                        shelf_query
+                            .iter()
+                            .filter(|(_, _, shelf)| {
+                                shelf.stock > 0
+                                    && shopping_list.remaining(shelf.main_item, inventory) > 0
+                            })
+                            .min_by(|(_, a, _), (_, b, _)| {
+                                distance_to(a).total_cmp(&distance_to(b))
+                            })
+                            .map(|(shelf_entity, _, _)| shelf_entity)
+                    });
+                    let Some(target_shelf) = closest_shelf else {
+                        *status = ActionStatus::Failure;
+                        return;
+                    };
+
+                    // Plan the trip from scratch
+                    nav_path.clear();
+                    traveling.target_shelf = Some(target_shelf);
+                    target_shelf
                 }
+            };
+
+            // Look elsewhere if the shelf was emptied on the way
+            let Some((_, shelf_transform, _)) = shelf_query
+                .get(target_shelf)
+                .ok()
+                .filter(|(_, _, shelf)| shelf.stock > 0)
+            else {
+                traveling.target_shelf = None;
+                return;
+            };
+
+            if shopper.current_shelf == Some(target_shelf) {
+                *status = ActionStatus::Success;
+                return;
             }
+
+            follow_path(
+                &nav_grid,
+                &mut shopper_transform,
+                &mut shopper_impulse,
+                &mut nav_path,
+                steering,
+                target_shelf,
+                shelf_transform.translation.truncate(),
+            );
         },
     );
 }
 
 fn shopper_taking(
     time: Res<Time>,
-    mut shopper_query: Query<(&mut Inventory, &mut ShopperState), With<Shopper>>,
+    mut shopper_query: Query<(
+        &mut Taking,
+        &mut ActionStatus,
+        &Shopper,
+        &mut Inventory,
+        &ShoppingList,
+    )>,
+    mut shelf_query: Query<&mut Shelf>,
+) {
+    // Shoppers share shelf stock, so this can't run in parallel
+    for (mut taking, mut status, shopper, mut inventory, shopping_list) in shopper_query.iter_mut()
+    {
+        if !status.is_running() {
+            continue;
+        }
+
+        let Some(mut shelf) = shopper
+            .current_shelf
+            .and_then(|shelf_entity| shelf_query.get_mut(shelf_entity).ok())
+        else {
+            // Got pushed away from the shelf
+            *status = ActionStatus::Failure;
+            continue;
+        };
+
+        let taking_interval = shopper.archetype.taking_interval;
+        let taking_timer = taking
+            .taking_timer
+            .get_or_insert_with(|| Timer::from_seconds(taking_interval, TimerMode::Repeating));
+
+        // Only take what's on the list
+        if taking_timer.tick(time.delta()).just_finished()
+            && shopping_list.remaining(shelf.main_item, &inventory) > 0
+            && shelf.take()
+        {
+            // Add the shelf's main item to the shopper's inventory
+            inventory
+                .0
+                .entry(shelf.main_item)
+                .and_modify(|e| *e += 1)
+                .or_insert(1);
+        }
+
+        if shelf.stock == 0 || shopping_list.remaining(shelf.main_item, &inventory) == 0 {
+            *status = ActionStatus::Success;
+        }
+    }
+}
+
+/// Picks a counter for every shopper that's ready to line up.
+///
+/// Runs one shopper at a time, so a crowd finishing their shopping together spreads out over
+/// the counters instead of all picking the same one.
+fn shopper_choosing_counter(
+    mut shopper_query: Query<(
+        &mut ActionStatus,
+        &mut Shopper,
+        Option<&ShoppingList>,
+        &Inventory,
+        &Transform,
+        Has<Queuing>,
+    )>,
+    player_query: Query<&Player>,
+    counter_query: Query<(Entity, &Transform), With<CheckoutCounter>>,
     shelf_query: Query<&Shelf>,
 ) {
-    shopper_query
-        .par_iter_mut()
-        .for_each(|(mut inventory, mut shopper_state)| {
-            if let ShopperState::Taking {
-                timer: _,
-                ref mut taking_timer,
-                target_shelf,
-            } = *shopper_state
-            {
-                if let Ok(shelf) = shelf_query.get(target_shelf) {
-                    if taking_timer.tick(time.delta()).just_finished() {
-                        // Add the shelf's main item to the shopper's inventory
-                        inventory
-                            .0
-                            .entry(shelf.main_item)
-                            .and_modify(|e| *e += 1)
-                            .or_insert(1);
-                    }
-                }
-            }
-        });
+    let carried_items = carried_items(shelf_query.iter());
+
+    // Count every claim on a counter, including ones that haven't joined its queue yet
+    let mut line_lengths = HashMap::<Entity, usize>::new();
+    let claims = player_query
+        .iter()
+        .filter_map(|player| player.queued_counter)
+        .chain(
+            shopper_query
+                .iter()
+                .filter_map(|(_, shopper, ..)| shopper.queued_counter),
+        );
+    for counter in claims {
+        *line_lengths.entry(counter).or_default() += 1;
+    }
+
+    for (mut status, mut shopper, shopping_list, inventory, shopper_transform, queuing) in
+        shopper_query.iter_mut()
+    {
+        if !queuing || !status.is_running() || shopper.queued_counter.is_some() {
+            continue;
+        }
+
+        // Only line up with a full basket
+        let ready_to_pay = !inventory.0.is_empty()
+            && shopping_list
+                .is_some_and(|shopping_list| shopping_list.is_done(inventory, &carried_items));
+        if !ready_to_pay {
+            *status = ActionStatus::Failure;
+            continue;
+        }
+
+        // Pick the counter with the shortest line, preferring closer ones
+        let line_length = |counter: Entity| line_lengths.get(&counter).copied().unwrap_or(0);
+        let distance_to = |transform: &Transform| {
+            shopper_transform
+                .translation
+                .distance_squared(transform.translation)
+        };
+        let shortest_queue = counter_query
+            .iter()
+            .min_by(|(a, a_transform), (b, b_transform)| {
+                line_length(*a)
+                    .cmp(&line_length(*b))
+                    .then(distance_to(a_transform).total_cmp(&distance_to(b_transform)))
+            })
+            .map(|(counter_entity, _)| counter_entity);
+        let Some(counter) = shortest_queue else {
+            *status = ActionStatus::Failure;
+            continue;
+        };
+
+        shopper.queued_counter = Some(counter);
+        *line_lengths.entry(counter).or_default() += 1;
+    }
 }
 
-fn shopper_panicked(
-    mut shopper_query: Query<(&mut Transform, &mut ExternalImpulse, &ShopperState), With<Shopper>>,
-    player_query: Single<&Transform, (With<Player>, Without<Shopper>)>,
+fn shopper_queuing(
+    mut shopper_query: Query<
+        (
+            Entity,
+            &mut ActionStatus,
+            &mut Shopper,
+            &mut Transform,
+            &mut ExternalImpulse,
+        ),
+        (With<Queuing>, With<Shopper>),
+    >,
+    counter_query: Query<(Entity, &Transform, &CheckoutCounter), Without<Shopper>>,
 ) {
-    let panic_impulse = 5000.0;
+    let queue_impulse = 400.0;
 
     shopper_query.par_iter_mut().for_each(
-        |(mut shopper_transform, mut shopper_impulse, shopper_state)| {
-            if let ShopperState::Panicked = *shopper_state {
-                // Calculate direction towards the player
-                let direction = (player_query.translation - shopper_transform.translation)
-                    .truncate()
-                    .normalize_or_zero();
-
-                // Stampede the player lmao
+        |(shopper_entity, mut status, mut shopper, mut shopper_transform, mut shopper_impulse)| {
+            if !status.is_running() {
+                return;
+            }
+
+            // No counter was free to pick
+            let Some(counter) = shopper.queued_counter else {
+                *status = ActionStatus::Failure;
+                return;
+            };
+
+            let Ok((_, counter_transform, counter_data)) = counter_query.get(counter) else {
+                // Counter is gone, find another one
+                shopper.queued_counter = None;
+                return;
+            };
+            let Some(index) = counter_data.queue.iter().position(|e| *e == shopper_entity) else {
+                return;
+            };
+
+            // Shuffle towards our spot in the line
+            let offset = queue_slot_position(counter_transform, index)
+                - shopper_transform.translation.truncate();
+            if offset.length() > QUEUE_SLOT_TOLERANCE {
+                let direction = offset.normalize_or_zero();
                 shopper_transform.rotation = Quat::from_rotation_z(direction.to_angle());
-                shopper_impulse.apply_impulse(direction * panic_impulse);
+                shopper_impulse.apply_impulse(direction * queue_impulse);
+            } else if index == 0 {
+                // Our turn at the counter
+                *status = ActionStatus::Success;
             }
         },
     );
 }
 
-fn shopper_state_machine(
+fn shopper_paying(
     time: Res<Time>,
-    mut shopper_query: Query<(&Transform, &Shopper, &mut ShopperState)>,
-    shelf_query: Query<(Entity, &Transform), With<Shelf>>,
+    mut shopper_query: Query<(
+        &mut Paying,
+        &mut ActionStatus,
+        &mut Shopper,
+        &mut Inventory,
+        &mut ShoppingList,
+    )>,
+    counter_query: Query<&CheckoutCounter>,
 ) {
-    shopper_query
-        .par_iter_mut()
-        .for_each(|(shopper_transform, shopper, mut shopper_state)| {
-            match *shopper_state {
-                ShopperState::Wandering {
-                    ref mut timer,
-                    direction: _,
-                } => {
-                    // If shopper is about to panic, just panic
-                    if shopper.panic_meter >= PANIC_THRESHOLD {
-                        // Transition to panicked state
-                        *shopper_state = ShopperState::Panicked;
-                        return;
-                    }
+    shopper_query.par_iter_mut().for_each(
+        |(mut paying, mut status, mut shopper, mut inventory, mut shopping_list)| {
+            if !status.is_running() {
+                return;
+            }
+
+            let Some(counter) = shopper
+                .queued_counter
+                .and_then(|counter| counter_query.get(counter).ok())
+            else {
+                *status = ActionStatus::Failure;
+                return;
+            };
 
-                    if !timer.tick(time.delta()).just_finished() {
-                        // Continue wandering
+            let checkout_duration = counter.checkout_duration;
+            let timer = paying
+                .timer
+                .get_or_insert_with(|| Timer::from_seconds(checkout_duration, TimerMode::Once));
+            if !timer.tick(time.delta()).just_finished() {
+                // Continue paying
+                return;
+            }
+
+            // Bag the items and head home
+            inventory.0.clear();
+            shopping_list.0.clear();
+            shopper.queued_counter = None;
+            *status = ActionStatus::Success;
+        },
+    );
+}
+
+fn shopper_leaving(
+    nav_grid: Res<NavGrid>,
+    par_commands: ParallelCommands,
+    mut shopper_query: Query<
+        (
+            Entity,
+            &mut Leaving,
+            &mut ActionStatus,
+            Option<&ShoppingList>,
+            &Inventory,
+            &mut Transform,
+            &mut ExternalImpulse,
+            &mut NavPath,
+            &Steering,
+        ),
+        With<Shopper>,
+    >,
+    exit_query: Query<(Entity, &Transform), (With<Exit>, Without<Shopper>)>,
+    shelf_query: Query<&Shelf>,
+) {
+    let carried_items = carried_items(shelf_query.iter());
+
+    shopper_query.par_iter_mut().for_each(
+        |(
+            shopper_entity,
+            mut leaving,
+            mut status,
+            shopping_list,
+            inventory,
+            mut shopper_transform,
+            mut shopper_impulse,
+            mut nav_path,
+            steering,
+        )| {
+            if !status.is_running() {
+                return;
+            }
+
+            let exit = match leaving.exit {
+                Some(exit) => exit,
+                None => {
+                    // Only leave once there's nothing left to find or pay for
+                    let done_shopping = inventory.0.is_empty()
+                        && shopping_list.is_some_and(|shopping_list| {
+                            shopping_list.is_done(inventory, &carried_items)
+                        });
+                    if !done_shopping {
+                        *status = ActionStatus::Failure;
                         return;
                     }
 
-                    // Choose a random shelf to travel to
-                    let target_shelf = {
-                        // Choose a random shelf from the 5 closests shelves
-                        let mut closest_shelves = shelf_query
-                            .iter()
-                            .map(|(shelf_entity, shelf_transform)| {
-                                let distance = shopper_transform
-                                    .translation
-                                    .distance_squared(shelf_transform.translation);
-                                (shelf_entity, distance)
-                            })
-                            .collect::<Vec<_>>();
-                        closest_shelves.sort_by_key(|(_, distance)| distance.round() as u32);
-                        fastrand::choice(closest_shelves.iter().take(5)).unwrap().0
+                    let distance_to = |transform: &Transform| {
+                        shopper_transform
+                            .translation
+                            .distance_squared(transform.translation)
+                    };
+                    let closest_exit = exit_query
+                        .iter()
+                        .min_by(|(_, a), (_, b)| distance_to(a).total_cmp(&distance_to(b)))
+                        .map(|(exit_entity, _)| exit_entity);
+                    let Some(exit) = closest_exit else {
+                        *status = ActionStatus::Failure;
+                        return;
                     };
 
-                    // Transition to traveling to the random shelf
-                    *shopper_state = ShopperState::Traveling { target_shelf };
-                }
-                ShopperState::Traveling { target_shelf } => {
-                    // Transition to taking from the shelf if reached the target shelf
-                    if shopper.current_shelf.is_some_and(|s| s == target_shelf) {
-                        *shopper_state = ShopperState::Taking {
-                            timer: Timer::from_seconds(8.0, TimerMode::Once),
-                            taking_timer: Timer::from_seconds(1.5, TimerMode::Repeating),
-                            target_shelf,
-                        };
-                    }
+                    // Plan the trip from scratch
+                    nav_path.clear();
+                    leaving.exit = Some(exit);
+                    exit
                 }
-                ShopperState::Taking {
-                    ref mut timer,
-                    taking_timer: _,
-                    target_shelf: _,
-                } => {
-                    if !timer.tick(time.delta()).just_finished() {
-                        // Continue taking
-                        return;
-                    }
+            };
 
-                    // Chose a random direction to wander off towards
-                    let wander_direction =
-                        Vec2::new(fastrand::f32() * 2.0 - 1.0, fastrand::f32() * 2.0 - 1.0)
-                            .normalize_or_zero();
+            let Ok((_, exit_transform)) = exit_query.get(exit) else {
+                *status = ActionStatus::Failure;
+                return;
+            };
+            let exit_position = exit_transform.translation.truncate();
 
-                    *shopper_state = ShopperState::Wandering {
-                        timer: Timer::from_seconds(10.0, TimerMode::Once),
-                        direction: wander_direction,
+            if shopper_transform
+                .translation
+                .truncate()
+                .distance(exit_position)
+                <= EXIT_RADIUS
+            {
+                log::debug!("Shopper {} left the store", shopper_entity);
+                par_commands.command_scope(|mut commands| {
+                    commands.entity(shopper_entity).despawn();
+                });
+                return;
+            }
+
+            follow_path(
+                &nav_grid,
+                &mut shopper_transform,
+                &mut shopper_impulse,
+                &mut nav_path,
+                steering,
+                exit,
+                exit_position,
+            );
+        },
+    );
+}
+
+fn shopper_grabbing(
+    mut shopper_query: Query<
+        (
+            &mut Grabbing,
+            &mut ActionStatus,
+            &mut Transform,
+            &mut ExternalImpulse,
+        ),
+        With<Shopper>,
+    >,
+    dropped_item_query: Query<(Entity, &Transform), (With<DroppedItem>, Without<Shopper>)>,
+) {
+    let grab_impulse = 1000.0;
+
+    shopper_query.par_iter_mut().for_each(
+        |(mut grabbing, mut status, mut shopper_transform, mut shopper_impulse)| {
+            if !status.is_running() {
+                return;
+            }
+
+            let target_item = match grabbing.target_item {
+                Some(target_item) => target_item,
+                None => {
+                    let Some(target_item) =
+                        nearby_dropped_item(&shopper_transform, &dropped_item_query)
+                    else {
+                        *status = ActionStatus::Failure;
+                        return;
                     };
+                    grabbing.target_item = Some(target_item);
+                    target_item
                 }
-                ShopperState::Panicked => {
-                    // Do nothing, stay in panic state.
-                }
+            };
+
+            // Someone else got there first, or we picked it up
+            let Ok((_, item_transform)) = dropped_item_query.get(target_item) else {
+                *status = ActionStatus::Success;
+                return;
+            };
+
+            // Calculate direction to the dropped item
+            let direction = (item_transform.translation - shopper_transform.translation)
+                .truncate()
+                .normalize_or_zero();
+
+            shopper_transform.rotation = Quat::from_rotation_z(direction.to_angle());
+            shopper_impulse.apply_impulse(direction * grab_impulse);
+        },
+    );
+}
+
+fn shopper_fleeing(
+    mut shopper_query: Query<
+        (
+            &mut ActionStatus,
+            &Shopper,
+            &mut Transform,
+            &mut ExternalImpulse,
+        ),
+        With<Fleeing>,
+    >,
+    player_query: Single<&Transform, (With<Player>, Without<Shopper>)>,
+) {
+    let flee_impulse = 1500.0;
+
+    shopper_query.par_iter_mut().for_each(
+        |(mut status, shopper, mut shopper_transform, mut shopper_impulse)| {
+            if !status.is_running() {
+                return;
             }
-        });
+
+            // Calmed down enough to go back to shopping, or too worked up to keep running
+            if shopper.panic_tier() != PanicTier::Alarmed {
+                *status = ActionStatus::Failure;
+                return;
+            }
+
+            // Calculate direction away from the player
+            let direction = (shopper_transform.translation - player_query.translation)
+                .truncate()
+                .normalize_or_zero();
+
+            shopper_transform.rotation = Quat::from_rotation_z(direction.to_angle());
+            shopper_impulse.apply_impulse(direction * flee_impulse);
+        },
+    );
+}
+
+fn shopper_panicked(
+    mut shopper_query: Query<
+        (
+            &mut ActionStatus,
+            &Shopper,
+            &mut Transform,
+            &mut ExternalImpulse,
+        ),
+        With<Panicked>,
+    >,
+    player_query: Single<&Transform, (With<Player>, Without<Shopper>)>,
+) {
+    let panic_impulse = 5000.0;
+
+    shopper_query.par_iter_mut().for_each(
+        |(mut status, shopper, mut shopper_transform, mut shopper_impulse)| {
+            if !status.is_running() {
+                return;
+            }
+
+            // Calmed down enough to stop charging
+            if !shopper.is_stampeding() {
+                *status = ActionStatus::Failure;
+                return;
+            }
+
+            // Calculate direction towards the player
+            let direction = (player_query.translation - shopper_transform.translation)
+                .truncate()
+                .normalize_or_zero();
+
+            // Stampede the player lmao
+            shopper_transform.rotation = Quat::from_rotation_z(direction.to_angle());
+            shopper_impulse.apply_impulse(direction * panic_impulse);
+        },
+    );
+}
+
+fn random_wander_direction(rng: &mut Rng) -> Vec2 {
+    Vec2::new(rng.f32() * 2.0 - 1.0, rng.f32() * 2.0 - 1.0).normalize_or_zero()
+}
+
+#[cfg(feature = "dev")]
+fn draw_vision_cones(mut gizmos: Gizmos, shopper_query: Query<(&Transform, &Shopper)>) {
+    for (shopper_transform, shopper) in shopper_query.iter() {
+        let position = shopper_transform.translation.truncate();
+        let facing = (shopper_transform.rotation * Vec3::X).truncate().to_angle();
+        let color = shopper.panic_tier().indicator_color().with_alpha(0.4);
+
+        // Arcs are drawn counter-clockwise starting from +Y
+        let arc_start = facing - VISION_HALF_ANGLE - std::f32::consts::FRAC_PI_2;
+        gizmos.arc_2d(
+            Isometry2d::new(position, Rot2::radians(arc_start)),
+            2.0 * VISION_HALF_ANGLE,
+            PANIC_DISTANCE,
+            color,
+        );
+        for edge in [facing - VISION_HALF_ANGLE, facing + VISION_HALF_ANGLE] {
+            gizmos.line_2d(
+                position,
+                position + Vec2::from_angle(edge) * PANIC_DISTANCE,
+                color,
+            );
+        }
+    }
 }
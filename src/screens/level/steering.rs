@@ -0,0 +1,131 @@
+use crate::screens::level::{GameLayer, LevelState, player::Player};
+use avian2d::prelude::*;
+use bevy::prelude::*;
+
+/// Agents closer than this get pushed apart.
+const SEPARATION_RADIUS: f32 = 90.0;
+/// Agents within this distance try to match each other's velocity.
+const MATCHING_RADIUS: f32 = 160.0;
+/// Velocity difference at which velocity matching steers at full strength.
+const MATCHING_SPEED: f32 = 100.0;
+/// How far ahead agents look for shelves and counters to steer around.
+const LOOKAHEAD_DISTANCE: f32 = 120.0;
+/// Room agents that avoid the player try to keep around them.
+const PERSONAL_SPACE: f32 = 140.0;
+
+const SEPARATION_WEIGHT: f32 = 1.5;
+const MATCHING_WEIGHT: f32 = 0.3;
+const AVOIDANCE_WEIGHT: f32 = 1.2;
+const PERSONAL_SPACE_WEIGHT: f32 = 1.0;
+
+/// Local avoidance for NPCs moving through a crowd.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct Steering {
+    /// Correction blended into the agent's goal direction, recomputed every frame.
+    pub force: Vec2,
+    /// Whether to keep some personal space around the player.
+    pub avoid_player: bool,
+}
+
+impl Steering {
+    /// Combines the direction the agent wants to go with the avoidance correction.
+    pub fn steer(&self, goal_direction: Vec2) -> Vec2 {
+        (goal_direction + self.force).normalize_or(goal_direction)
+    }
+}
+
+pub fn plugin(app: &mut App) {
+    // Register necessary types
+    app.register_type::<Steering>();
+
+    // Steering systems
+    app.add_systems(
+        FixedUpdate,
+        update_steering.run_if(in_state(LevelState::Playing)),
+    );
+}
+
+fn update_steering(
+    spatial_query: SpatialQuery,
+    mut agent_query: Query<(Entity, &Transform, &LinearVelocity, &mut Steering)>,
+    sensor_query: Query<(), With<Sensor>>,
+    player_query: Option<Single<&Transform, (With<Player>, Without<Steering>)>>,
+) {
+    let agents = agent_query
+        .iter()
+        .map(|(entity, transform, velocity, _)| {
+            (entity, transform.translation.truncate(), velocity.0)
+        })
+        .collect::<Vec<_>>();
+    let player_position = player_query.map(|transform| transform.translation.truncate());
+    let obstacle_filter = SpatialQueryFilter::from_mask(GameLayer::Environment);
+
+    agent_query.par_iter_mut().for_each(
+        |(agent_entity, agent_transform, agent_velocity, mut steering)| {
+            let position = agent_transform.translation.truncate();
+
+            // Separation and velocity matching with nearby agents
+            let mut separation = Vec2::ZERO;
+            let mut neighbor_velocity = Vec2::ZERO;
+            let mut neighbor_count = 0;
+            for &(other_entity, other_position, other_velocity) in agents.iter() {
+                if other_entity == agent_entity {
+                    continue;
+                }
+
+                let offset = position - other_position;
+                let distance = offset.length();
+                if distance > 0.0 && distance < SEPARATION_RADIUS {
+                    // Push harder the closer the other agent is
+                    separation += offset / distance * (1.0 - distance / SEPARATION_RADIUS);
+                }
+                if distance < MATCHING_RADIUS {
+                    neighbor_velocity += other_velocity;
+                    neighbor_count += 1;
+                }
+            }
+
+            let matching = if neighbor_count > 0 {
+                let average_velocity = neighbor_velocity / neighbor_count as f32;
+                ((average_velocity - agent_velocity.0) / MATCHING_SPEED).clamp_length_max(1.0)
+            } else {
+                Vec2::ZERO
+            };
+
+            // Slide around shelves and counters that are coming up ahead
+            let mut avoidance = Vec2::ZERO;
+            if let Ok(heading) = Dir2::new(agent_velocity.0) {
+                if let Some(hit) = spatial_query.cast_ray_predicate(
+                    position,
+                    heading,
+                    LOOKAHEAD_DISTANCE,
+                    true,
+                    &obstacle_filter,
+                    &|entity| !sensor_query.contains(entity),
+                ) {
+                    let mut tangent = hit.normal.perp();
+                    if tangent.dot(*heading) < 0.0 {
+                        tangent = -tangent;
+                    }
+                    avoidance = (hit.normal + tangent) * (1.0 - hit.distance / LOOKAHEAD_DISTANCE);
+                }
+            }
+
+            // Keep out of the player's way
+            let mut personal_space = Vec2::ZERO;
+            if let Some(player_position) = player_position.filter(|_| steering.avoid_player) {
+                let offset = position - player_position;
+                let distance = offset.length();
+                if distance > 0.0 && distance < PERSONAL_SPACE {
+                    personal_space = offset / distance * (1.0 - distance / PERSONAL_SPACE);
+                }
+            }
+
+            steering.force = separation * SEPARATION_WEIGHT
+                + matching * MATCHING_WEIGHT
+                + avoidance * AVOIDANCE_WEIGHT
+                + personal_space * PERSONAL_SPACE_WEIGHT;
+        },
+    );
+}
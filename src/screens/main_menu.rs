@@ -1,4 +1,8 @@
-use crate::{GameAssets, screens::Screen};
+use crate::{
+    GameAssets,
+    screens::Screen,
+    ui::{ButtonClicked, button},
+};
 use bevy::{color::palettes::css::*, prelude::*};
 
 #[derive(Component)]
@@ -7,6 +11,9 @@ struct MainMenuUI;
 #[derive(Component)]
 struct PlayButton;
 
+#[derive(Component)]
+struct SettingsButton;
+
 #[derive(Component)]
 struct QuitButton;
 
@@ -14,29 +21,11 @@ pub fn plugin(app: &mut App) {
     // Menu systems
     app.add_systems(OnEnter(Screen::MainMenu), spawn_main_menu);
     app.add_systems(OnExit(Screen::MainMenu), despawn_main_menu);
-    app.add_systems(
-        Update,
-        (play_button_interaction, quit_button_interaction).run_if(in_state(Screen::MainMenu)),
-    );
-}
-
-fn button(label: impl Into<String>, font: Handle<Font>, size: f32) -> impl Bundle {
-    let label_str = label.into();
 
-    (
-        Name::new(format!("{} Button", &label_str)),
-        Button,
-        Text::new(&label_str),
-        TextFont {
-            font: font,
-            font_size: size,
-            ..Default::default()
-        },
-        TextLayout {
-            justify: JustifyText::Center,
-            ..Default::default()
-        },
-    )
+    // Button reactions
+    app.add_observer(play_button_clicked);
+    app.add_observer(settings_button_clicked);
+    app.add_observer(quit_button_clicked);
 }
 
 fn spawn_main_menu(mut commands: Commands, assets: Res<GameAssets>) {
@@ -76,6 +65,10 @@ fn spawn_main_menu(mut commands: Commands, assets: Res<GameAssets>) {
                 },
                 children![
                     (PlayButton, button("Play", assets.ui_font.clone(), 32.0)),
+                    (
+                        SettingsButton,
+                        button("Settings", assets.ui_font.clone(), 32.0)
+                    ),
                     (QuitButton, button("Quit", assets.ui_font.clone(), 32.0))
                 ]
             )
@@ -87,38 +80,34 @@ fn despawn_main_menu(mut commands: Commands, query: Single<Entity, With<MainMenu
     commands.entity(query.entity()).despawn();
 }
 
-fn play_button_interaction(
+fn play_button_clicked(
+    trigger: Trigger<ButtonClicked>,
+    mut next_screen: ResMut<NextState<Screen>>,
+    query: Query<(), With<PlayButton>>,
+) {
+    if query.contains(trigger.target()) {
+        log::info!("Starting game...");
+        next_screen.set(Screen::Level);
+    }
+}
+
+fn settings_button_clicked(
+    trigger: Trigger<ButtonClicked>,
     mut next_screen: ResMut<NextState<Screen>>,
-    mut query: Single<(&Interaction, &mut TextColor), (Changed<Interaction>, With<PlayButton>)>,
+    query: Query<(), With<SettingsButton>>,
 ) {
-    match *query.0 {
-        Interaction::Pressed => {
-            log::info!("Starting game...");
-            next_screen.set(Screen::Level);
-        }
-        Interaction::Hovered => {
-            *query.1 = SLATE_GRAY.into();
-        }
-        Interaction::None => {
-            *query.1 = WHITE.into();
-        }
+    if query.contains(trigger.target()) {
+        next_screen.set(Screen::Settings);
     }
 }
 
-fn quit_button_interaction(
+fn quit_button_clicked(
+    trigger: Trigger<ButtonClicked>,
     mut events: EventWriter<AppExit>,
-    mut query: Single<(&Interaction, &mut TextColor), (Changed<Interaction>, With<QuitButton>)>,
+    query: Query<(), With<QuitButton>>,
 ) {
-    match *query.0 {
-        Interaction::Pressed => {
-            log::info!("Exiting app...");
-            events.write(AppExit::Success);
-        }
-        Interaction::Hovered => {
-            *query.1 = SLATE_GRAY.into();
-        }
-        Interaction::None => {
-            *query.1 = WHITE.into();
-        }
+    if query.contains(trigger.target()) {
+        log::info!("Exiting app...");
+        events.write(AppExit::Success);
     }
 }
@@ -0,0 +1,397 @@
+use crate::{
+    GameAssets,
+    screens::Screen,
+    storage::{Persistent, Storage},
+    ui::{ButtonClicked, button},
+};
+use bevy::{
+    audio::Volume,
+    color::palettes::css::*,
+    prelude::*,
+    window::{MonitorSelection, PrimaryWindow, VideoModeSelection, WindowMode},
+};
+use serde::{Deserialize, Serialize};
+
+/// Volume changes by this much every time a volume option is clicked.
+const VOLUME_STEP: f32 = 0.1;
+const UI_SCALES: [f32; 4] = [0.75, 1.0, 1.25, 1.5];
+
+/// Player preferences, read by the systems they affect.
+#[derive(Resource, Clone, PartialEq, Debug, Serialize, Deserialize)]
+// Options added later fall back to their defaults in older saves
+#[serde(default)]
+pub struct Settings {
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub display_mode: DisplayMode,
+    pub ui_scale: f32,
+    pub interact_mode: InteractMode,
+    pub difficulty: Difficulty,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            master_volume: 1.0,
+            music_volume: 1.0,
+            sfx_volume: 1.0,
+            display_mode: DisplayMode::Windowed,
+            ui_scale: 1.0,
+            interact_mode: InteractMode::Hold,
+            difficulty: Difficulty::Normal,
+        }
+    }
+}
+
+impl Persistent for Settings {
+    const KEY: &'static str = "settings";
+    const VERSION: u32 = 1;
+}
+
+/// Volume for music, on top of the master volume. Music players should be spawned with it.
+#[derive(Resource, Deref, Clone, Copy, Debug)]
+pub struct MusicVolume(pub Volume);
+
+/// Volume for sound effects, on top of the master volume. Sound effects should be spawned
+/// with it.
+#[derive(Resource, Deref, Clone, Copy, Debug)]
+pub struct SfxVolume(pub Volume);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum DisplayMode {
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+impl DisplayMode {
+    fn next(self) -> Self {
+        match self {
+            Self::Windowed => Self::Borderless,
+            Self::Borderless => Self::Fullscreen,
+            Self::Fullscreen => Self::Windowed,
+        }
+    }
+}
+
+impl std::fmt::Display for DisplayMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str_repr = match self {
+            Self::Windowed => "Windowed",
+            Self::Borderless => "Borderless",
+            Self::Fullscreen => "Fullscreen",
+        };
+        write!(f, "{}", str_repr)
+    }
+}
+
+/// How the interact button grabs items off a shelf.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum InteractMode {
+    /// Keep grabbing while the button is held.
+    Hold,
+    /// Start grabbing on one press and stop on the next.
+    Toggle,
+}
+
+impl std::fmt::Display for InteractMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str_repr = match self {
+            Self::Hold => "Hold",
+            Self::Toggle => "Toggle",
+        };
+        write!(f, "{}", str_repr)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    fn next(self) -> Self {
+        match self {
+            Self::Easy => Self::Normal,
+            Self::Normal => Self::Hard,
+            Self::Hard => Self::Easy,
+        }
+    }
+
+    /// Multiplier applied to the level's time limit.
+    pub fn time_limit_scale(self) -> f32 {
+        match self {
+            Self::Easy => 1.5,
+            Self::Normal => 1.0,
+            Self::Hard => 0.75,
+        }
+    }
+}
+
+impl std::fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str_repr = match self {
+            Self::Easy => "Easy",
+            Self::Normal => "Normal",
+            Self::Hard => "Hard",
+        };
+        write!(f, "{}", str_repr)
+    }
+}
+
+#[derive(Component)]
+struct SettingsUI;
+
+/// A button that cycles through the values of one setting when clicked.
+#[derive(Component, Clone, Copy)]
+enum SettingsOption {
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    DisplayMode,
+    UiScale,
+    InteractMode,
+    Difficulty,
+}
+
+impl SettingsOption {
+    const ALL: [Self; 7] = [
+        Self::MasterVolume,
+        Self::MusicVolume,
+        Self::SfxVolume,
+        Self::DisplayMode,
+        Self::UiScale,
+        Self::InteractMode,
+        Self::Difficulty,
+    ];
+
+    fn label(self, settings: &Settings) -> String {
+        let percent = |volume: f32| format!("{}%", (volume * 100.0).round());
+        match self {
+            Self::MasterVolume => format!("Master Volume: {}", percent(settings.master_volume)),
+            Self::MusicVolume => format!("Music Volume: {}", percent(settings.music_volume)),
+            Self::SfxVolume => format!("SFX Volume: {}", percent(settings.sfx_volume)),
+            Self::DisplayMode => format!("Display: {}", settings.display_mode),
+            Self::UiScale => format!("UI Scale: {}x", settings.ui_scale),
+            Self::InteractMode => format!("Interact: {}", settings.interact_mode),
+            Self::Difficulty => format!("Difficulty: {}", settings.difficulty),
+        }
+    }
+
+    fn cycle(self, settings: &mut Settings) {
+        // Snaps to whole steps, and wraps back around to silent after full volume
+        let step_volume = |volume: f32| {
+            let next = ((volume / VOLUME_STEP).round() + 1.0) * VOLUME_STEP;
+            if next > 1.0 + VOLUME_STEP / 2.0 {
+                0.0
+            } else {
+                next
+            }
+        };
+        match self {
+            Self::MasterVolume => settings.master_volume = step_volume(settings.master_volume),
+            Self::MusicVolume => settings.music_volume = step_volume(settings.music_volume),
+            Self::SfxVolume => settings.sfx_volume = step_volume(settings.sfx_volume),
+            Self::DisplayMode => settings.display_mode = settings.display_mode.next(),
+            Self::UiScale => {
+                let current = UI_SCALES
+                    .iter()
+                    .position(|scale| *scale == settings.ui_scale)
+                    .unwrap_or(0);
+                settings.ui_scale = UI_SCALES[(current + 1) % UI_SCALES.len()];
+            }
+            Self::InteractMode => {
+                settings.interact_mode = match settings.interact_mode {
+                    InteractMode::Hold => InteractMode::Toggle,
+                    InteractMode::Toggle => InteractMode::Hold,
+                }
+            }
+            Self::Difficulty => settings.difficulty = settings.difficulty.next(),
+        }
+    }
+}
+
+#[derive(Component)]
+struct ControlsButton;
+
+/// Leaves the settings menu, going back to wherever it was opened from.
+#[derive(Component)]
+pub struct SettingsBackButton;
+
+pub fn plugin(app: &mut App) {
+    // Add resources
+    let settings = app
+        .world()
+        .resource::<Storage>()
+        .load_or_default::<Settings>();
+    app.insert_resource(MusicVolume(Volume::Linear(settings.music_volume)));
+    app.insert_resource(SfxVolume(Volume::Linear(settings.sfx_volume)));
+    app.insert_resource(settings);
+
+    // Menu systems
+    app.add_systems(OnEnter(Screen::Settings), spawn_settings_menu);
+    app.add_systems(OnExit(Screen::Settings), despawn_settings_menu);
+    app.add_systems(
+        Update,
+        (
+            update_settings_labels,
+            apply_display_settings,
+            apply_audio_settings,
+        )
+            .run_if(resource_changed::<Settings>),
+    );
+
+    // Button reactions
+    app.add_observer(settings_option_clicked);
+    app.add_observer(controls_button_clicked);
+    app.add_observer(back_button_clicked);
+}
+
+/// Also used by the pause menu, which opens the settings without leaving the round.
+pub fn spawn_settings_menu(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    settings: Res<Settings>,
+    screen: Res<State<Screen>>,
+) {
+    commands
+        .spawn((
+            Name::new("Settings UI"),
+            SettingsUI,
+            Node {
+                width: Val::Vw(100.0),
+                height: Val::Vh(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            BackgroundColor(Color::BLACK.with_alpha(0.6)),
+            // Draw over the level when opened from the pause menu
+            GlobalZIndex(1),
+            children![(
+                Name::new("Title"),
+                Text::new("Settings"),
+                TextColor(WHITE.into()),
+                TextFont {
+                    font: assets.ui_font.clone(),
+                    font_size: 48.0,
+                    ..Default::default()
+                },
+                TextLayout {
+                    justify: JustifyText::Center,
+                    ..Default::default()
+                },
+            )],
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    Name::new("Button Container"),
+                    Node {
+                        flex_direction: FlexDirection::Column,
+                        padding: UiRect::vertical(Val::Px(32.0)),
+                        row_gap: Val::Px(12.0),
+                        ..Default::default()
+                    },
+                ))
+                .with_children(|parent| {
+                    for option in SettingsOption::ALL {
+                        parent.spawn((
+                            option,
+                            button(option.label(&settings), assets.ui_font.clone(), 24.0),
+                        ));
+                    }
+                    // Rebinding only happens outside of a round, since the player's actions are
+                    // built when it spawns
+                    if *screen.get() == Screen::Settings {
+                        parent.spawn((
+                            ControlsButton,
+                            button("Controls", assets.ui_font.clone(), 24.0),
+                        ));
+                    }
+                    parent.spawn((
+                        SettingsBackButton,
+                        button("Back", assets.ui_font.clone(), 32.0),
+                    ));
+                });
+        });
+}
+
+pub fn despawn_settings_menu(mut commands: Commands, query: Single<Entity, With<SettingsUI>>) {
+    commands.entity(query.entity()).despawn();
+}
+
+fn update_settings_labels(settings: Res<Settings>, mut query: Query<(&SettingsOption, &mut Text)>) {
+    for (option, mut text) in query.iter_mut() {
+        text.0 = option.label(&settings);
+    }
+}
+
+fn apply_display_settings(
+    settings: Res<Settings>,
+    mut ui_scale: ResMut<UiScale>,
+    mut window: Single<&mut Window, With<PrimaryWindow>>,
+) {
+    ui_scale.0 = settings.ui_scale;
+
+    let window_mode = match settings.display_mode {
+        DisplayMode::Windowed => WindowMode::Windowed,
+        DisplayMode::Borderless => WindowMode::BorderlessFullscreen(MonitorSelection::Current),
+        DisplayMode::Fullscreen => {
+            WindowMode::Fullscreen(MonitorSelection::Current, VideoModeSelection::Current)
+        }
+    };
+    if window.mode != window_mode {
+        window.mode = window_mode;
+    }
+}
+
+fn apply_audio_settings(
+    settings: Res<Settings>,
+    mut global_volume: ResMut<GlobalVolume>,
+    mut music_volume: ResMut<MusicVolume>,
+    mut sfx_volume: ResMut<SfxVolume>,
+) {
+    global_volume.volume = Volume::Linear(settings.master_volume);
+    music_volume.0 = Volume::Linear(settings.music_volume);
+    sfx_volume.0 = Volume::Linear(settings.sfx_volume);
+}
+
+fn settings_option_clicked(
+    trigger: Trigger<ButtonClicked>,
+    mut settings: ResMut<Settings>,
+    storage: Res<Storage>,
+    query: Query<&SettingsOption>,
+) {
+    if let Ok(option) = query.get(trigger.target()) {
+        option.cycle(&mut settings);
+        log::info!("Changed settings: {:?}", *settings);
+        storage.save_or_log(&*settings);
+    }
+}
+
+fn controls_button_clicked(
+    trigger: Trigger<ButtonClicked>,
+    mut next_screen: ResMut<NextState<Screen>>,
+    query: Query<(), With<ControlsButton>>,
+) {
+    if query.contains(trigger.target()) {
+        next_screen.set(Screen::Controls);
+    }
+}
+
+fn back_button_clicked(
+    trigger: Trigger<ButtonClicked>,
+    screen: Res<State<Screen>>,
+    mut next_screen: ResMut<NextState<Screen>>,
+    query: Query<(), With<SettingsBackButton>>,
+) {
+    // The pause menu handles going back when the settings were opened mid-round
+    if query.contains(trigger.target()) && *screen.get() == Screen::Settings {
+        next_screen.set(Screen::MainMenu);
+    }
+}
@@ -1,4 +1,8 @@
-use crate::{GameAssets, screens::Screen};
+use crate::{
+    GameAssets,
+    screens::{ReplaySeed, RunSeed, Screen},
+    ui::{ButtonClicked, button},
+};
 use bevy::{color::palettes::css::*, prelude::*};
 
 #[derive(Component)]
@@ -7,36 +11,20 @@ struct WinScreenUI;
 #[derive(Component)]
 struct PlayAgainButton;
 
+#[derive(Component)]
+struct ReplaySeedButton;
+
 pub fn plugin(app: &mut App) {
     // Menu systems
     app.add_systems(OnEnter(Screen::Win), spawn_win_screen);
     app.add_systems(OnExit(Screen::Win), despawn_win_screen);
-    app.add_systems(
-        Update,
-        play_again_button_interaction.run_if(in_state(Screen::Win)),
-    );
-}
 
-fn button(label: impl Into<String>, font: Handle<Font>, size: f32) -> impl Bundle {
-    let label_str = label.into();
-
-    (
-        Name::new(format!("{} Button", &label_str)),
-        Button,
-        Text::new(&label_str),
-        TextFont {
-            font: font,
-            font_size: size,
-            ..Default::default()
-        },
-        TextLayout {
-            justify: JustifyText::Center,
-            ..Default::default()
-        },
-    )
+    // Button reactions
+    app.add_observer(play_again_button_clicked);
+    app.add_observer(replay_seed_button_clicked);
 }
 
-fn spawn_win_screen(mut commands: Commands, assets: Res<GameAssets>) {
+fn spawn_win_screen(mut commands: Commands, assets: Res<GameAssets>, run_seed: Res<RunSeed>) {
     commands.spawn((
         Name::new("Win Screen UI"),
         WinScreenUI,
@@ -77,6 +65,20 @@ fn spawn_win_screen(mut commands: Commands, assets: Res<GameAssets>) {
                     ..Default::default()
                 },
             ),
+            (
+                Name::new("Seed"),
+                Text::new(format!("Seed: {}", run_seed.0)),
+                TextColor(SLATE_GRAY.into()),
+                TextFont {
+                    font: assets.ui_font.clone(),
+                    font_size: 16.0,
+                    ..Default::default()
+                },
+                TextLayout {
+                    justify: JustifyText::Center,
+                    ..Default::default()
+                },
+            ),
             (
                 Name::new("Button Container"),
                 Node {
@@ -85,10 +87,16 @@ fn spawn_win_screen(mut commands: Commands, assets: Res<GameAssets>) {
                     row_gap: Val::Px(12.0),
                     ..Default::default()
                 },
-                children![(
-                    PlayAgainButton,
-                    button("Play Again", assets.ui_font.clone(), 32.0)
-                ),]
+                children![
+                    (
+                        PlayAgainButton,
+                        button("Play Again", assets.ui_font.clone(), 32.0)
+                    ),
+                    (
+                        ReplaySeedButton,
+                        button("Replay Seed", assets.ui_font.clone(), 32.0)
+                    ),
+                ]
             )
         ],
     ));
@@ -98,23 +106,27 @@ fn despawn_win_screen(mut commands: Commands, query: Single<Entity, With<WinScre
     commands.entity(query.entity()).despawn();
 }
 
-fn play_again_button_interaction(
+fn play_again_button_clicked(
+    trigger: Trigger<ButtonClicked>,
+    mut next_screen: ResMut<NextState<Screen>>,
+    query: Query<(), With<PlayAgainButton>>,
+) {
+    if query.contains(trigger.target()) {
+        log::info!("Starting game...");
+        next_screen.set(Screen::Level);
+    }
+}
+
+fn replay_seed_button_clicked(
+    trigger: Trigger<ButtonClicked>,
+    run_seed: Res<RunSeed>,
+    mut replay_seed: ResMut<ReplaySeed>,
     mut next_screen: ResMut<NextState<Screen>>,
-    mut query: Single<
-        (&Interaction, &mut TextColor),
-        (Changed<Interaction>, With<PlayAgainButton>),
-    >,
+    query: Query<(), With<ReplaySeedButton>>,
 ) {
-    match *query.0 {
-        Interaction::Pressed => {
-            log::info!("Starting game...");
-            next_screen.set(Screen::Level);
-        }
-        Interaction::Hovered => {
-            *query.1 = SLATE_GRAY.into();
-        }
-        Interaction::None => {
-            *query.1 = WHITE.into();
-        }
+    if query.contains(trigger.target()) {
+        log::info!("Replaying seed {}...", run_seed.0);
+        replay_seed.0 = Some(run_seed.0);
+        next_screen.set(Screen::Level);
     }
 }
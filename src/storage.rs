@@ -0,0 +1,303 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use thiserror::Error;
+
+/// Folder (or key prefix on the web) that everything is saved under.
+const APP_NAME: &str = "toilet-paper";
+
+/// Data that's kept between sessions.
+pub trait Persistent: Serialize + DeserializeOwned + Default {
+    /// Name the data is saved under.
+    const KEY: &'static str;
+    /// Bump whenever the saved format changes, and upgrade older saves in [`Persistent::migrate`].
+    const VERSION: u32;
+
+    /// Upgrades a save written by an older `version`, given its full contents.
+    fn migrate(version: u32, _contents: &str) -> Result<Self, StorageError> {
+        Err(StorageError::UnsupportedVersion {
+            key: Self::KEY,
+            version,
+        })
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum StorageError {
+    #[error("Could not access storage: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse saved data: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("Could not serialize data: {0}")]
+    Serialize(#[from] ron::Error),
+    #[error("Saved {key} has unsupported version {version}")]
+    UnsupportedVersion { key: &'static str, version: u32 },
+    #[cfg(target_arch = "wasm32")]
+    #[error("Browser storage is unavailable")]
+    Unavailable,
+}
+
+/// Somewhere to keep saved data, by key.
+pub trait StorageBackend: Send + Sync + 'static {
+    /// Contents saved under `key`, or `None` if nothing was saved yet.
+    fn read(&self, key: &str) -> Result<Option<String>, StorageError>;
+    fn write(&self, key: &str, contents: &str) -> Result<(), StorageError>;
+}
+
+/// Saves every key as a `.ron` file in a directory.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct FileStorage {
+    dir: std::path::PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileStorage {
+    /// Any directory works, e.g. a temporary one when trying out save formats.
+    pub fn new(dir: impl Into<std::path::PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Storage in the platform's config directory, or the working directory if there isn't one.
+    pub fn in_config_dir() -> Self {
+        let Some(config_dir) = config_dir() else {
+            log::warn!("Could not find a config directory, saving to the working directory");
+            return Self::new(APP_NAME);
+        };
+        Self::new(config_dir.join(APP_NAME))
+    }
+
+    fn path(&self, key: &str) -> std::path::PathBuf {
+        self.dir.join(format!("{}.ron", key))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StorageBackend for FileStorage {
+    fn read(&self, key: &str) -> Result<Option<String>, StorageError> {
+        match std::fs::read_to_string(self.path(key)) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn write(&self, key: &str, contents: &str) -> Result<(), StorageError> {
+        std::fs::create_dir_all(&self.dir)?;
+
+        // Write next to the old save first, so a crash mid-write can't corrupt it
+        let path = self.path(key);
+        let temp_path = path.with_extension("ron.tmp");
+        std::fs::write(&temp_path, contents)?;
+        std::fs::rename(temp_path, path)?;
+        Ok(())
+    }
+}
+
+/// The platform's per-user config directory.
+#[cfg(not(target_arch = "wasm32"))]
+fn config_dir() -> Option<std::path::PathBuf> {
+    use std::{env, path::PathBuf};
+
+    cfg_if::cfg_if! {
+        if #[cfg(target_os = "windows")] {
+            env::var_os("APPDATA").map(PathBuf::from)
+        } else if #[cfg(target_os = "macos")] {
+            env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+        } else {
+            // Respecting XDG_CONFIG_HOME also lets it be pointed at a temporary directory
+            env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .filter(|dir| dir.is_absolute())
+                .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        }
+    }
+}
+
+/// Saves every key in the browser's `localStorage`.
+#[cfg(target_arch = "wasm32")]
+pub struct LocalStorage;
+
+#[cfg(target_arch = "wasm32")]
+impl LocalStorage {
+    fn storage() -> Result<web_sys::Storage, StorageError> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or(StorageError::Unavailable)
+    }
+
+    fn item_key(key: &str) -> String {
+        format!("{}/{}", APP_NAME, key)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl StorageBackend for LocalStorage {
+    fn read(&self, key: &str) -> Result<Option<String>, StorageError> {
+        Self::storage()?
+            .get_item(&Self::item_key(key))
+            .map_err(|_| StorageError::Unavailable)
+    }
+
+    fn write(&self, key: &str, contents: &str) -> Result<(), StorageError> {
+        Self::storage()?
+            .set_item(&Self::item_key(key), contents)
+            .map_err(|_| StorageError::Unavailable)
+    }
+}
+
+#[derive(Serialize)]
+struct VersionedRef<'a, T> {
+    version: u32,
+    data: &'a T,
+}
+
+#[derive(Deserialize)]
+struct Versioned<T> {
+    data: T,
+}
+
+/// Just the version of a save, read before deciding how to parse the rest of it.
+#[derive(Deserialize)]
+struct VersionHeader {
+    /// Saves from before versioning have no header, and count as version 0.
+    #[serde(default)]
+    version: u32,
+}
+
+/// Where [`Persistent`] data is loaded from and saved to.
+#[derive(Resource)]
+pub struct Storage(Box<dyn StorageBackend>);
+
+impl Storage {
+    pub fn new(backend: impl StorageBackend) -> Self {
+        Self(Box::new(backend))
+    }
+
+    /// Storage for the platform the game is running on.
+    pub fn platform() -> Self {
+        cfg_if::cfg_if! {
+            if #[cfg(target_arch = "wasm32")] {
+                Self::new(LocalStorage)
+            } else {
+                Self::new(FileStorage::in_config_dir())
+            }
+        }
+    }
+
+    /// Loads saved data, migrating it if it was saved by an older version.
+    pub fn load<T: Persistent>(&self) -> Result<Option<T>, StorageError> {
+        let Some(contents) = self.0.read(T::KEY)? else {
+            return Ok(None);
+        };
+
+        let VersionHeader { version } = ron::from_str(&contents)?;
+        let data = match version.cmp(&T::VERSION) {
+            std::cmp::Ordering::Equal => ron::from_str::<Versioned<T>>(&contents)?.data,
+            std::cmp::Ordering::Less => {
+                log::info!("Migrating {} from version {}", T::KEY, version);
+                T::migrate(version, &contents)?
+            }
+            // Saved by a newer build, there's no way to tell what changed
+            std::cmp::Ordering::Greater => {
+                return Err(StorageError::UnsupportedVersion {
+                    key: T::KEY,
+                    version,
+                });
+            }
+        };
+        Ok(Some(data))
+    }
+
+    /// Loads saved data, falling back to defaults if there isn't any or it can't be read.
+    pub fn load_or_default<T: Persistent>(&self) -> T {
+        match self.load() {
+            Ok(data) => data.unwrap_or_default(),
+            Err(error) => {
+                log::warn!("Could not load {}, using defaults: {}", T::KEY, error);
+                T::default()
+            }
+        }
+    }
+
+    pub fn save<T: Persistent>(&self, data: &T) -> Result<(), StorageError> {
+        let versioned = VersionedRef {
+            version: T::VERSION,
+            data,
+        };
+        let contents = ron::ser::to_string_pretty(&versioned, ron::ser::PrettyConfig::default())?;
+        self.0.write(T::KEY, &contents)
+    }
+
+    /// Saves data, logging instead of returning errors since there's rarely anything to do
+    /// about them.
+    pub fn save_or_log<T: Persistent>(&self, data: &T) {
+        if let Err(error) = self.save(data) {
+            log::error!("Could not save {}: {}", T::KEY, error);
+        }
+    }
+}
+
+pub fn plugin(app: &mut App) {
+    // Add resources
+    app.insert_resource(Storage::platform());
+}
+
+#[cfg(test)]
+impl FileStorage {
+    /// Empty storage in its own temporary directory, for tests.
+    pub fn temp(name: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("{}-{}-{}", APP_NAME, name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        Self::new(dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Default, PartialEq, Debug)]
+    struct Counter {
+        count: u32,
+    }
+
+    impl Persistent for Counter {
+        const KEY: &'static str = "counter";
+        const VERSION: u32 = 2;
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let backend = FileStorage::temp("newer-version");
+        backend
+            .write(Counter::KEY, "(version: 3, data: (count: 1))")
+            .unwrap();
+        let storage = Storage::new(backend);
+
+        assert!(matches!(
+            storage.load::<Counter>(),
+            Err(StorageError::UnsupportedVersion {
+                key: "counter",
+                version: 3
+            })
+        ));
+        assert_eq!(storage.load_or_default::<Counter>(), Counter::default());
+    }
+
+    #[test]
+    fn older_versions_without_a_migration_are_rejected() {
+        let backend = FileStorage::temp("older-version");
+        backend
+            .write(Counter::KEY, "(version: 1, data: (count: 1))")
+            .unwrap();
+        let storage = Storage::new(backend);
+
+        assert!(matches!(
+            storage.load::<Counter>(),
+            Err(StorageError::UnsupportedVersion {
+                key: "counter",
+                version: 1
+            })
+        ));
+    }
+}
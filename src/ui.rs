@@ -0,0 +1,263 @@
+use bevy::{color::palettes::css::*, prelude::*};
+use bevy_enhanced_input::prelude::*;
+
+const NORMAL_COLOR: Srgba = WHITE;
+const HOVERED_COLOR: Srgba = SLATE_GRAY;
+const PRESSED_COLOR: Srgba = DARK_SLATE_GRAY;
+const DISABLED_COLOR: Srgba = DIM_GRAY;
+const FOCUS_OUTLINE_COLOR: Srgba = GOLD;
+
+#[derive(InputContext)]
+struct MenuInputContext;
+
+#[derive(Debug, InputAction)]
+#[input_action(output = bool)]
+struct FocusNext;
+
+#[derive(Debug, InputAction)]
+#[input_action(output = bool)]
+struct FocusPrevious;
+
+#[derive(Debug, InputAction)]
+#[input_action(output = bool)]
+struct Confirm;
+
+/// A menu button that can be clicked, or focused and confirmed with a keyboard or gamepad.
+#[derive(Component, Default)]
+pub struct UiButton {
+    /// Disabled buttons can't be focused or clicked.
+    pub disabled: bool,
+}
+
+/// Marks the button that keyboard and gamepad input goes to.
+#[derive(Component)]
+pub struct Focused;
+
+/// Triggered on a [`UiButton`] when it's clicked or confirmed while focused.
+#[derive(Event)]
+pub struct ButtonClicked;
+
+pub fn plugin(app: &mut App) {
+    app.add_input_context::<MenuInputContext>();
+
+    // Button systems
+    app.add_systems(
+        Update,
+        (
+            toggle_menu_actions,
+            (button_interaction, focus_first_button, button_style).chain(),
+        ),
+    );
+
+    // Menu input reactions
+    app.add_observer(focus_next);
+    app.add_observer(focus_previous);
+    app.add_observer(confirm_focused);
+}
+
+pub fn button(label: impl Into<String>, font: Handle<Font>, size: f32) -> impl Bundle {
+    let label_str = label.into();
+
+    (
+        Name::new(format!("{} Button", &label_str)),
+        UiButton::default(),
+        Button,
+        Text::new(&label_str),
+        TextColor(NORMAL_COLOR.into()),
+        TextFont {
+            font,
+            font_size: size,
+            ..Default::default()
+        },
+        TextLayout {
+            justify: JustifyText::Center,
+            ..Default::default()
+        },
+        Outline::new(Val::Px(2.0), Val::Px(4.0), Color::NONE),
+    )
+}
+
+/// Menu input only exists while a menu is on screen, since its keys overlap the gameplay
+/// controls.
+fn toggle_menu_actions(
+    mut commands: Commands,
+    button_query: Query<(), With<UiButton>>,
+    actions_query: Query<Entity, With<Actions<MenuInputContext>>>,
+) {
+    match (button_query.is_empty(), actions_query.single()) {
+        (false, Err(_)) => spawn_menu_actions(&mut commands),
+        (true, Ok(entity)) => commands.entity(entity).despawn(),
+        _ => {}
+    }
+}
+
+fn spawn_menu_actions(commands: &mut Commands) {
+    let mut actions = Actions::<MenuInputContext>::default();
+    actions
+        .bind::<FocusNext>()
+        .to((
+            KeyCode::ArrowDown,
+            KeyCode::KeyS,
+            KeyCode::Tab,
+            GamepadButton::DPadDown,
+        ))
+        .with_conditions(Press::default());
+    actions
+        .bind::<FocusPrevious>()
+        .to((KeyCode::ArrowUp, KeyCode::KeyW, GamepadButton::DPadUp))
+        .with_conditions(Press::default());
+    actions
+        .bind::<Confirm>()
+        .to((KeyCode::Enter, KeyCode::Space, GamepadButton::South))
+        .with_conditions(Press::default());
+
+    commands.spawn((Name::new("Menu Input"), actions));
+}
+
+/// Moves focus to `target`, taking it away from every other button.
+fn set_focus(
+    commands: &mut Commands,
+    focused_query: &Query<Entity, With<Focused>>,
+    target: Entity,
+) {
+    for entity in focused_query.iter().filter(|entity| *entity != target) {
+        commands.entity(entity).remove::<Focused>();
+    }
+    commands.entity(target).insert(Focused);
+}
+
+/// Enabled buttons in the order focus moves through them, top to bottom and left to right.
+fn focus_order(
+    button_query: &Query<(Entity, &UiButton, &ComputedNode, &GlobalTransform)>,
+) -> Vec<Entity> {
+    let mut buttons = button_query
+        .iter()
+        // Buttons that haven't been laid out yet have no position to sort by
+        .filter(|(_, button, node, _)| !button.disabled && node.size() != Vec2::ZERO)
+        .map(|(entity, _, _, transform)| (entity, transform.translation().truncate()))
+        .collect::<Vec<_>>();
+    buttons.sort_by(|(_, a), (_, b)| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)));
+    buttons.into_iter().map(|(entity, _)| entity).collect()
+}
+
+fn button_interaction(
+    mut commands: Commands,
+    button_query: Query<(Entity, &Interaction, &UiButton), Changed<Interaction>>,
+    focused_query: Query<Entity, With<Focused>>,
+) {
+    for (entity, interaction, button) in button_query.iter() {
+        if button.disabled {
+            continue;
+        }
+
+        match interaction {
+            Interaction::Pressed => commands.trigger_targets(ButtonClicked, entity),
+            // Keep the mouse and keyboard pointing at the same button
+            Interaction::Hovered => set_focus(&mut commands, &focused_query, entity),
+            Interaction::None => {}
+        }
+    }
+}
+
+/// Focuses the first button of a freshly spawned menu, so it can be used without a mouse.
+fn focus_first_button(
+    mut commands: Commands,
+    button_query: Query<(Entity, &UiButton, &ComputedNode, &GlobalTransform)>,
+    focused_query: Query<Entity, With<Focused>>,
+) {
+    let has_focus = focused_query.iter().any(|entity| {
+        button_query
+            .get(entity)
+            .is_ok_and(|(_, button, _, _)| !button.disabled)
+    });
+    if has_focus {
+        return;
+    }
+
+    if let Some(&first) = focus_order(&button_query).first() {
+        set_focus(&mut commands, &focused_query, first);
+    }
+}
+
+fn button_style(
+    mut button_query: Query<(
+        &UiButton,
+        &Interaction,
+        Has<Focused>,
+        &mut TextColor,
+        &mut Outline,
+    )>,
+) {
+    for (button, interaction, focused, mut text_color, mut outline) in button_query.iter_mut() {
+        let color = Color::from(match (button.disabled, interaction) {
+            (true, _) => DISABLED_COLOR,
+            (false, Interaction::Pressed) => PRESSED_COLOR,
+            (false, Interaction::Hovered) => HOVERED_COLOR,
+            (false, Interaction::None) => NORMAL_COLOR,
+        });
+        let outline_color = if focused && !button.disabled {
+            FOCUS_OUTLINE_COLOR.into()
+        } else {
+            Color::NONE
+        };
+
+        if text_color.0 != color {
+            text_color.0 = color;
+        }
+        if outline.color != outline_color {
+            outline.color = outline_color;
+        }
+    }
+}
+
+/// Moves focus `step` buttons along the focus order, wrapping around at either end.
+fn move_focus(
+    commands: &mut Commands,
+    button_query: &Query<(Entity, &UiButton, &ComputedNode, &GlobalTransform)>,
+    focused_query: &Query<Entity, With<Focused>>,
+    step: isize,
+) {
+    let order = focus_order(button_query);
+    if order.is_empty() {
+        return;
+    }
+
+    let current = focused_query
+        .iter()
+        .find_map(|focused| order.iter().position(|entity| *entity == focused));
+    let next = match current {
+        Some(index) => (index as isize + step).rem_euclid(order.len() as isize) as usize,
+        None => 0,
+    };
+    set_focus(commands, focused_query, order[next]);
+}
+
+fn focus_next(
+    _trigger: Trigger<Fired<FocusNext>>,
+    mut commands: Commands,
+    button_query: Query<(Entity, &UiButton, &ComputedNode, &GlobalTransform)>,
+    focused_query: Query<Entity, With<Focused>>,
+) {
+    move_focus(&mut commands, &button_query, &focused_query, 1);
+}
+
+fn focus_previous(
+    _trigger: Trigger<Fired<FocusPrevious>>,
+    mut commands: Commands,
+    button_query: Query<(Entity, &UiButton, &ComputedNode, &GlobalTransform)>,
+    focused_query: Query<Entity, With<Focused>>,
+) {
+    move_focus(&mut commands, &button_query, &focused_query, -1);
+}
+
+fn confirm_focused(
+    _trigger: Trigger<Fired<Confirm>>,
+    mut commands: Commands,
+    focused_query: Query<(Entity, &UiButton), With<Focused>>,
+) {
+    for (entity, button) in focused_query.iter() {
+        if !button.disabled {
+            commands.trigger_targets(ButtonClicked, entity);
+        }
+    }
+}